@@ -0,0 +1,106 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use alvarium_annotator::{derive_hash, serialise_and_sign, HashProvider};
+use alvarium_rust_sdk::annotations::{constants, Annotation, Annotator, PayloadAnnotator};
+use alvarium_rust_sdk::config::{SdkInfo, Signable};
+use alvarium_rust_sdk::errors::{Error, Result};
+use alvarium_rust_sdk::factories::{new_annotator, new_hash_provider, new_payload_extractor, new_signature_provider};
+use alvarium_rust_sdk::providers::sign_provider::SignatureProviderWrap;
+
+// Per message overhead of an SDK call with the default annotators, comparing the annotators as
+// they were, each building its hash provider, parsing the payload and looking up the hostname on
+// every call, against the SDK extracting the payload once for annotators built with the rest
+
+// The annotate path every annotator shared before its resources were cached, the TLS annotator
+// has no connection so is unsatisfied either way
+struct BaselineAnnotator {
+    hash: constants::HashType,
+    kind: constants::AnnotationType,
+    sign: SignatureProviderWrap,
+}
+
+impl Annotator for BaselineAnnotator {
+    type Error = Error;
+    fn annotate(&mut self, data: &[u8]) -> Result<Annotation> {
+        let hasher = new_hash_provider(&self.hash)?;
+        let signable: std::result::Result<Signable, serde_json::Error> = serde_json::from_slice(data);
+        let (satisfied, key) = match signable {
+            Ok(signable) => {
+                let key = derive_hash(hasher, signable.seed.as_bytes());
+                match self.kind.kind() {
+                    "pki" => (signable.verify_signature(&self.sign)?, key),
+                    "tls" => (false, key),
+                    _ => (true, key),
+                }
+            },
+            Err(_) => (false, derive_hash(hasher, data)),
+        };
+        match gethostname::gethostname().to_str() {
+            Some(host) => {
+                let mut annotation = Annotation::new(&key, self.hash.clone(), host, self.kind.clone(), satisfied);
+                let signature = serialise_and_sign(&self.sign, &annotation)?;
+                annotation.with_signature(&signature);
+                Ok(annotation)
+            },
+            None => Err(Error::NoHostName)
+        }
+    }
+}
+
+fn config() -> SdkInfo {
+    let config_bytes = std::fs::read("resources/test_config.json").unwrap();
+    serde_json::from_slice(&config_bytes).unwrap()
+}
+
+fn message() -> Vec<u8> {
+    let signable = Signable::new("Some random data".repeat(64), hex::encode([0u8; 64]));
+    serde_json::to_vec(&signable).unwrap()
+}
+
+fn hash_provider(c: &mut Criterion) {
+    let cfg = config();
+    let data = message();
+    let mut group = c.benchmark_group("hash_provider");
+
+    group.bench_function("rebuilt_per_call", |b| b.iter(|| {
+        let hasher = new_hash_provider(&cfg.hash.hash_type).unwrap();
+        derive_hash(hasher, black_box(&data))
+    }));
+
+    let hasher = new_hash_provider(&cfg.hash.hash_type).unwrap();
+    group.bench_function("cached", |b| b.iter(|| hasher.derive(black_box(&data))));
+    group.finish();
+}
+
+fn per_message(c: &mut Criterion) {
+    let cfg = config();
+    let data = message();
+    let mut baseline = cfg.annotators.iter()
+        .map(|kind| BaselineAnnotator {
+            hash: cfg.hash.hash_type.clone(),
+            kind: kind.clone(),
+            sign: new_signature_provider(&cfg.signature).unwrap(),
+        })
+        .collect::<Vec<_>>();
+    let mut annotators = cfg.annotators.iter()
+        .map(|kind| new_annotator(kind.clone(), cfg.clone()).unwrap())
+        .collect::<Vec<_>>();
+    let extractor = new_payload_extractor(&cfg.payload);
+    let mut group = c.benchmark_group("per_message");
+
+    group.bench_function("baseline", |b| b.iter(|| {
+        baseline.iter_mut()
+            .map(|annotator| annotator.annotate(black_box(&data)).unwrap())
+            .collect::<Vec<_>>()
+    }));
+
+    group.bench_function("cached", |b| b.iter(|| {
+        let payload = extractor.extract(black_box(&data));
+        annotators.iter_mut()
+            .map(|annotator| annotator.annotate_payload(&data, &payload).unwrap())
+            .collect::<Vec<_>>()
+    }));
+    group.finish();
+}
+
+criterion_group!(benches, hash_provider, per_message);
+criterion_main!(benches);
@@ -0,0 +1,241 @@
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use criterion::{black_box, BenchmarkId, Criterion, Throughput};
+use serde_json::{json, Value};
+use alvarium_annotator::{HashProvider, MessageWrapper, Publisher, SignProvider};
+use alvarium_rust_sdk::annotations::constants;
+use alvarium_rust_sdk::config::{SdkInfo, Signable, StreamInfo};
+use alvarium_rust_sdk::errors::{Error, Result};
+use alvarium_rust_sdk::factories::{new_annotator, new_async_annotator, new_hash_provider, new_signature_provider};
+use alvarium_rust_sdk::sdk::SDK;
+
+// Throughput of the annotation and publishing paths, for sizing gateways. Besides the usual
+// criterion report, a summary of the benchmarks run is written as JSON to `summary.json` in the
+// criterion output directory, or to the path in `ALVARIUM_BENCH_JSON`
+
+const PAYLOAD_SIZES: [usize; 4] = [64, 1024, 16 * 1024, 256 * 1024];
+
+fn config() -> SdkInfo {
+    let config_bytes = std::fs::read("resources/test_config.json").unwrap();
+    serde_json::from_slice(&config_bytes).unwrap()
+}
+
+fn signed_message(size: usize) -> Vec<u8> {
+    let signable = Signable::new("a".repeat(size), hex::encode([0u8; 64]));
+    serde_json::to_vec(&signable).unwrap()
+}
+
+/// Keeps published messages in memory, so the end to end benchmark measures the SDK rather than
+/// a broker
+struct MemoryPublisher {
+    buffer: Vec<u8>,
+}
+
+#[async_trait::async_trait]
+impl Publisher for MemoryPublisher {
+    type StreamConfig = StreamInfo;
+    type Error = Error;
+    async fn new(_cfg: &StreamInfo) -> Result<Self> {
+        Ok(MemoryPublisher { buffer: Vec::new() })
+    }
+
+    async fn close(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn connect(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn reconnect(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn publish(&mut self, msg: MessageWrapper<'_>) -> Result<()> {
+        self.buffer.clear();
+        Ok(serde_json::to_writer(&mut self.buffer, &msg)?)
+    }
+}
+
+fn hash_providers(c: &mut Criterion) {
+    let mut group = c.benchmark_group("hash");
+    for kind in [&*constants::MD5_HASH, &*constants::SHA256_HASH, &*constants::NO_HASH] {
+        let hasher = new_hash_provider(kind).unwrap();
+        for size in PAYLOAD_SIZES {
+            let data = vec![b'a'; size];
+            group.throughput(Throughput::Bytes(size as u64));
+            group.bench_with_input(BenchmarkId::new(kind.0.as_str(), size), &data, |b, data| {
+                b.iter(|| hasher.derive(black_box(data)))
+            });
+        }
+    }
+    group.finish();
+}
+
+fn ed25519(c: &mut Criterion) {
+    let cfg = config();
+    let provider = new_signature_provider(&cfg.signature).unwrap();
+    let mut group = c.benchmark_group("ed25519");
+    for size in PAYLOAD_SIZES {
+        let data = vec![b'a'; size];
+        let signature = hex::decode(provider.sign(&data).unwrap()).unwrap();
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::new("sign", size), &data, |b, data| {
+            b.iter(|| provider.sign(black_box(data)).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("verify", size), &data, |b, data| {
+            b.iter(|| provider.verify(black_box(data), &signature).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn annotators(c: &mut Criterion) {
+    let mut cfg = config();
+    cfg.schema.path = Some("resources/schemas/reading.json".to_string());
+    // Remembers every nonce of a run, a full cache would turn new nonces away as well
+    cfg.freshness.cache_size = 1_000_000;
+    let kinds = [
+        &*constants::ANNOTATION_SOURCE,
+        &*constants::ANNOTATION_PKI,
+        &*constants::ANNOTATION_TLS,
+        &*constants::ANNOTATION_TPM,
+        &*constants::ANNOTATION_FRESHNESS,
+        &*constants::ANNOTATION_SCHEMA,
+        &*constants::ANNOTATION_EXECUTABLE,
+        &*constants::ANNOTATION_CONTAINER,
+    ];
+
+    let mut group = c.benchmark_group("annotate");
+    // Each message carries its own nonce, so freshness is measured on its accepting path. The
+    // counter outlives the closure, which criterion calls again for warm up and every sample
+    let mut nonce = 0u64;
+    for kind in kinds {
+        let mut annotator = new_annotator(kind.clone(), cfg.clone()).unwrap();
+        group.bench_function(kind.kind(), |b| {
+            b.iter(|| {
+                nonce += 1;
+                let data = reading(nonce);
+                annotator.annotate(black_box(&data)).unwrap()
+            })
+        });
+        if kind == &*constants::ANNOTATION_FRESHNESS {
+            nonce += 1;
+            assert!(annotator.annotate(&reading(nonce)).unwrap().is_satisfied, "replay cache turned away a new nonce");
+        }
+    }
+    group.finish();
+}
+
+fn reading(nonce: u64) -> Vec<u8> {
+    let reading = json!({
+        "temperature": 21.5,
+        "timestamp": chrono::Utc::now().timestamp(),
+        "nonce": nonce.to_string(),
+    });
+    let signable = Signable::new(reading.to_string(), hex::encode([0u8; 64]));
+    serde_json::to_vec(&signable).unwrap()
+}
+
+fn sdk_create(c: &mut Criterion) {
+    let cfg = config();
+    let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+    let mut annotators = cfg.annotators.iter()
+        .map(|kind| new_async_annotator(kind.clone(), cfg.clone()).unwrap())
+        .collect::<Vec<_>>();
+    let mut sdk: SDK<MemoryPublisher> = runtime.block_on(SDK::new(cfg.clone(), annotators.as_mut_slice())).unwrap();
+
+    let mut group = c.benchmark_group("sdk");
+    for size in PAYLOAD_SIZES {
+        let data = signed_message(size);
+        group.throughput(Throughput::Elements(1));
+        group.bench_with_input(BenchmarkId::new("create", size), &data, |b, data| {
+            b.iter(|| runtime.block_on(sdk.create(black_box(data))).unwrap())
+        });
+    }
+    group.finish();
+}
+
+// Resolved the way criterion resolves its output directory
+fn criterion_home() -> PathBuf {
+    if let Ok(home) = std::env::var("CRITERION_HOME") {
+        return PathBuf::from(home)
+    }
+    match std::env::var("CARGO_TARGET_DIR") {
+        Ok(target) => PathBuf::from(target).join("criterion"),
+        Err(_) => PathBuf::from("target/criterion"),
+    }
+}
+
+// Criterion leaves `new/benchmark.json` and `new/estimates.json` for every benchmark it ran,
+// those not written since `since` are left over from earlier runs
+fn collect_estimates(dir: &Path, since: SystemTime, summary: &mut Vec<Value>) {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if !path.is_dir() {
+            continue
+        }
+        if path.file_name().map_or(false, |name| name == "new") {
+            if let Some(result) = read_estimates(&path, since) {
+                summary.push(result)
+            }
+            continue
+        }
+        collect_estimates(&path, since, summary)
+    }
+}
+
+fn read_estimates(dir: &Path, since: SystemTime) -> Option<Value> {
+    let modified = std::fs::metadata(dir.join("estimates.json")).ok()?.modified().ok()?;
+    if modified < since {
+        return None
+    }
+
+    let read = |name: &str| -> Option<Value> {
+        serde_json::from_slice(&std::fs::read(dir.join(name)).ok()?).ok()
+    };
+    let benchmark = read("benchmark.json")?;
+    let estimates = read("estimates.json")?;
+    Some(json!({
+        "id": benchmark["full_id"],
+        "throughput": benchmark["throughput"],
+        "meanNs": estimates["mean"]["point_estimate"],
+        "medianNs": estimates["median"]["point_estimate"],
+        "stdDevNs": estimates["std_dev"]["point_estimate"],
+    }))
+}
+
+// Summarises the benchmarks of this run only, started at `since`
+fn write_summary(since: SystemTime) {
+    let home = criterion_home();
+    let mut summary = Vec::new();
+    collect_estimates(&home, since, &mut summary);
+    summary.sort_by(|a, b| a["id"].as_str().cmp(&b["id"].as_str()));
+
+    let path = match std::env::var("ALVARIUM_BENCH_JSON") {
+        Ok(path) => PathBuf::from(path),
+        Err(_) => home.join("summary.json"),
+    };
+    let report = json!({
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+        "benchmarks": summary,
+    });
+    if let Err(e) = std::fs::write(&path, serde_json::to_vec_pretty(&report).unwrap()) {
+        eprintln!("Could not write benchmark summary to {}: {}", path.display(), e)
+    }
+}
+
+fn main() {
+    let started = SystemTime::now();
+    let mut criterion = Criterion::default().configure_from_args();
+    hash_providers(&mut criterion);
+    ed25519(&mut criterion);
+    annotators(&mut criterion);
+    sdk_create(&mut criterion);
+    criterion.final_summary();
+    write_summary(started);
+}
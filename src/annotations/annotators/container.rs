@@ -0,0 +1,330 @@
+use std::sync::Arc;
+use std::collections::BTreeMap;
+use std::path::Path;
+use serde::{Serialize, Deserialize};
+use crate::annotations::{Annotation, Annotator, PayloadAnnotator, constants};
+use crate::config::{self, ContainerInfo};
+use alvarium_annotator::{serialise_and_sign, HashProvider};
+use crate::factories::{new_hash_provider, new_host_identity, new_payload_extractor, new_signature_provider};
+use crate::providers::payload_provider::{Payload, PayloadExtractorChain};
+use crate::providers::hash_provider::HashProviderWrapper;
+use crate::providers::sign_provider::SignatureProviderWrap;
+use crate::errors::{Error, Result};
+use log::info;
+
+// cgroup path segments left by the common container runtimes
+const RUNTIME_MARKERS: [&str; 6] = ["docker", "kubepods", "containerd", "libpod", "crio", "lxc"];
+
+/// What could be learned about the container the process runs in
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ContainerRuntime {
+    pub in_container: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub container_id: Option<String>,
+    /// Image digest in `sha256:<hex>` form
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image_digest: Option<String>,
+}
+
+impl ContainerRuntime {
+    /// Inspects the running system, see [`ContainerRuntime::detect_from`]
+    pub fn detect(cfg: &ContainerInfo) -> Self {
+        ContainerRuntime::detect_from(Path::new("/"), cfg, |name| std::env::var(name).ok())
+    }
+
+    /// Detects a container from `.dockerenv`, runtime markers in `proc/self/cgroup`, or PID 1
+    /// reporting a different PID in `proc/1/sched` (it sees its host PID when PID namespaced),
+    /// all relative to `root`. The container ID and image digest are taken from the configured
+    /// environment variables first, falling back to the cgroup and mount paths for the ID and to
+    /// the metadata file for the digest. The paths are only searched once in a container, as a
+    /// host has IDs in its own paths (e.g. Docker's image layers)
+    pub fn detect_from<E: Fn(&str) -> Option<String>>(root: &Path, cfg: &ContainerInfo, env: E) -> Self {
+        let read = |path: &str| std::fs::read_to_string(root.join(path)).unwrap_or_default();
+        let cgroup = read("proc/self/cgroup");
+
+        let in_container = root.join(".dockerenv").exists()
+            || cgroup.lines().any(|line| RUNTIME_MARKERS.iter().any(|marker| line.contains(marker)))
+            || sched_pid(&read("proc/1/sched")).map_or(false, |pid| pid != 1);
+
+        let container_id = env(&cfg.id_env)
+            .filter(|id| !id.is_empty())
+            .or_else(|| match in_container {
+                true => cgroup_container_id(&cgroup).or_else(|| mount_container_id(&read("proc/self/mountinfo"))),
+                false => None,
+            });
+
+        let image_digest = env(&cfg.digest_env)
+            .and_then(|digest| find_digest(&digest))
+            .or_else(|| {
+                let metadata = std::fs::read_to_string(cfg.metadata_path.as_ref()?).ok()?;
+                find_digest(&metadata)
+            });
+
+        ContainerRuntime { in_container, container_id, image_digest }
+    }
+
+    /// The container ID and image digest, as recorded in the context of container annotations
+    pub fn context(&self) -> BTreeMap<String, String> {
+        [("containerId", &self.container_id), ("imageDigest", &self.image_digest)].into_iter()
+            .filter_map(|(name, value)| Some((name.to_string(), value.clone()?)))
+            .collect()
+    }
+}
+
+// The first line of /proc/<pid>/sched reads "<comm> (<pid>, #threads: <n>)"
+fn sched_pid(sched: &str) -> Option<u32> {
+    let line = sched.lines().next()?;
+    let start = line.rfind('(')? + 1;
+    let end = start + line[start..].find(',')?;
+    line[start..end].trim().parse().ok()
+}
+
+// Container IDs are 64 hex characters, alone or wrapped as in `docker-<id>.scope` or
+// `cri-containerd-<id>.scope`
+fn container_id(component: &str) -> Option<String> {
+    component.split(|c: char| !c.is_ascii_hexdigit())
+        .find(|segment| segment.len() == 64)
+        .map(|id| id.to_lowercase())
+}
+
+// Only from cgroups named by a container runtime
+fn cgroup_container_id(cgroup: &str) -> Option<String> {
+    cgroup.lines()
+        .filter(|line| RUNTIME_MARKERS.iter().any(|marker| line.contains(marker)))
+        .find_map(|line| line.split('/').find_map(container_id))
+}
+
+// Only from a runtime's per container directory, such as the `/var/lib/docker/containers/<id>`
+// files bind mounted into the container, or a containerd namespace (`moby`, `k8s.io`)
+fn mount_container_id(mountinfo: &str) -> Option<String> {
+    mountinfo.split_whitespace()
+        .find_map(|path| {
+            let components = path.split('/').collect::<Vec<&str>>();
+            components.windows(2)
+                .filter(|pair| pair[0].ends_with("containers") || pair[0] == "moby" || pair[0] == "k8s.io")
+                .find_map(|pair| match pair[1].len() {
+                    64 => container_id(pair[1]),
+                    _ => None,
+                })
+        })
+}
+
+fn find_digest(contents: &str) -> Option<String> {
+    let start = contents.find("sha256:")?;
+    let hex: String = contents[start + 7..].chars()
+        .take_while(|c| c.is_ascii_hexdigit())
+        .collect();
+    match hex.len() {
+        64 => Some(format!("sha256:{}", hex.to_lowercase())),
+        _ => None,
+    }
+}
+
+pub struct ContainerAnnotator {
+    hash: constants::HashType,
+    kind: constants::AnnotationType,
+    hasher: HashProviderWrapper,
+    sign: SignatureProviderWrap,
+    host: Arc<str>,
+    payload: PayloadExtractorChain,
+    allow_list: Vec<String>,
+    require_container: bool,
+    runtime: ContainerRuntime,
+}
+
+impl ContainerAnnotator {
+    pub fn new(cfg: &config::SdkInfo) -> Result<impl PayloadAnnotator + Container> {
+        ContainerAnnotator::with_runtime(cfg, ContainerRuntime::detect(&cfg.container))
+    }
+
+    fn with_runtime(cfg: &config::SdkInfo, runtime: ContainerRuntime) -> Result<Self> {
+        info!("Container runtime detected: {:?}", runtime);
+        let allow_list = cfg.container.allow_list.iter()
+            .map(|digest| find_digest(digest).ok_or(Error::IncorrectConfig))
+            .collect::<Result<Vec<String>>>()?;
+
+        Ok(ContainerAnnotator {
+            hash: cfg.hash.hash_type.clone(),
+            hasher: new_hash_provider(&cfg.hash.hash_type)?,
+            kind: constants::ANNOTATION_CONTAINER.clone(),
+            sign: new_signature_provider(&cfg.signature)?,
+            host: new_host_identity(&cfg.host)?,
+            payload: new_payload_extractor(&cfg.payload),
+            allow_list,
+            require_container: cfg.container.require_container,
+            runtime,
+        })
+    }
+
+    fn check_runtime(&self) -> bool {
+        if self.require_container && !self.runtime.in_container {
+            return false
+        }
+        if self.allow_list.is_empty() {
+            return true
+        }
+        self.runtime.image_digest.as_ref().map_or(false, |digest| self.allow_list.contains(digest))
+    }
+}
+
+pub trait Container {
+    /// The container details detected when the annotator was created, for publishing alongside
+    /// the annotations
+    fn runtime(&self) -> &ContainerRuntime;
+}
+
+impl Container for ContainerAnnotator {
+    fn runtime(&self) -> &ContainerRuntime {
+        &self.runtime
+    }
+}
+
+impl Annotator for ContainerAnnotator {
+    type Error = crate::errors::Error;
+    fn annotate(&mut self, data: &[u8]) -> Result<Annotation> {
+        let payload = self.payload.extract(data);
+        self.annotate_payload(data, &payload)
+    }
+}
+
+impl PayloadAnnotator for ContainerAnnotator {
+    fn kind(&self) -> &constants::AnnotationType {
+        &self.kind
+    }
+
+    fn context(&self) -> BTreeMap<String, String> {
+        self.runtime.context()
+    }
+
+    fn annotate_payload(&mut self, data: &[u8], payload: &Payload) -> Result<Annotation> {
+        let mut annotation = self.annotate_unsigned(data, payload)?;
+        let signature = serialise_and_sign(&self.sign, &annotation)?;
+        annotation.with_signature(&signature);
+        Ok(annotation)
+    }
+
+    fn annotate_unsigned(&mut self, _data: &[u8], payload: &Payload) -> Result<Annotation> {
+        let key = self.hasher.derive(&payload.content);
+        Ok(Annotation::new(&key, self.hash.clone(), &self.host, self.kind.clone(), self.check_runtime()))
+    }
+}
+
+
+#[cfg(test)]
+mod container_tests {
+    use std::path::PathBuf;
+    use crate::config;
+    use crate::annotations::{Annotator, ContainerAnnotator, ContainerRuntime, PayloadAnnotator, constants};
+
+    const CONTAINER_ID: &str = "3f4e8a1c2b9d7e6f5a4b3c2d1e0f9a8b7c6d5e4f3a2b1c0d9e8f7a6b5c4d3e2f";
+    const DIGEST: &str = "sha256:0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+
+    fn fake_root(cgroup: &str, sched: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("alvarium_container_{}", ulid::Ulid::new()));
+        std::fs::create_dir_all(root.join("proc/self")).unwrap();
+        std::fs::create_dir_all(root.join("proc/1")).unwrap();
+        std::fs::write(root.join("proc/self/cgroup"), cgroup).unwrap();
+        std::fs::write(root.join("proc/1/sched"), sched).unwrap();
+        root
+    }
+
+    #[test]
+    fn detect_kubernetes_container() {
+        let config: config::SdkInfo = serde_json::from_slice(crate::CONFIG_BYTES.as_slice()).unwrap();
+        let cgroup = format!("0::/kubepods/besteffort/pod1234/cri-containerd-{}.scope\n", CONTAINER_ID);
+        let root = fake_root(&cgroup, "collector (1, #threads: 4)\n");
+
+        let runtime = ContainerRuntime::detect_from(&root, &config.container, |name| {
+            (name == "IMAGE_DIGEST").then(|| format!("registry.example.com/collector@{}", DIGEST))
+        });
+        assert!(runtime.in_container);
+        assert_eq!(runtime.container_id.as_deref(), Some(CONTAINER_ID));
+        assert_eq!(runtime.image_digest.as_deref(), Some(DIGEST));
+
+        let host = fake_root("0::/init.scope\n", "systemd (1, #threads: 1)\n");
+        let runtime = ContainerRuntime::detect_from(&host, &config.container, |_| None);
+        assert_eq!(runtime, ContainerRuntime::default());
+
+        let namespaced = fake_root("0::/\n", "sh (4182, #threads: 1)\n");
+        assert!(ContainerRuntime::detect_from(&namespaced, &config.container, |_| None).in_container);
+
+        for root in [root, host, namespaced] {
+            std::fs::remove_dir_all(root).unwrap();
+        }
+    }
+
+    #[test]
+    fn container_id_needs_runtime() {
+        let config: config::SdkInfo = serde_json::from_slice(crate::CONFIG_BYTES.as_slice()).unwrap();
+        let layer = "a".repeat(64);
+
+        // A Docker host has image layer paths of the same shape as container IDs
+        let host = fake_root("0::/user.slice/user-1000.slice/session-2.scope\n", "systemd (1, #threads: 1)\n");
+        std::fs::write(
+            host.join("proc/self/mountinfo"),
+            format!("412 29 0:52 / /var/lib/docker/overlay2/{}/merged rw - overlay overlay rw\n", layer)
+        ).unwrap();
+        let runtime = ContainerRuntime::detect_from(&host, &config.container, |_| None);
+        assert!(!runtime.in_container);
+        assert!(runtime.container_id.is_none());
+
+        // Inside a container, the ID comes from the runtime's own directory, not the image layers
+        let container = fake_root("0::/\n", "sh (4182, #threads: 1)\n");
+        std::fs::write(
+            container.join("proc/self/mountinfo"),
+            format!(
+                "601 600 0:52 / / rw - overlay overlay rw,upperdir=/var/lib/docker/overlay2/{}/diff\n\
+                 612 600 8:1 /var/lib/docker/containers/{}/hostname /etc/hostname rw - ext4 /dev/sda1 rw\n",
+                layer, CONTAINER_ID
+            )
+        ).unwrap();
+        let runtime = ContainerRuntime::detect_from(&container, &config.container, |_| None);
+        assert!(runtime.in_container);
+        assert_eq!(runtime.container_id.as_deref(), Some(CONTAINER_ID));
+
+        for root in [host, container] {
+            std::fs::remove_dir_all(root).unwrap();
+        }
+    }
+
+    #[test]
+    fn make_container_annotation() {
+        let mut config: config::SdkInfo = serde_json::from_slice(crate::CONFIG_BYTES.as_slice()).unwrap();
+        config.container.allow_list = vec![format!("registry.example.com/collector@{}", DIGEST)];
+        let runtime = ContainerRuntime {
+            in_container: true,
+            container_id: Some(CONTAINER_ID.to_string()),
+            image_digest: Some(DIGEST.to_string()),
+        };
+
+        let mut container_annotator = ContainerAnnotator::with_runtime(&config, runtime).unwrap();
+        let annotation = container_annotator.annotate("Some random data".as_bytes()).unwrap();
+
+        let context = container_annotator.context();
+        assert_eq!(context.get("containerId").map(String::as_str), Some(CONTAINER_ID));
+        assert_eq!(context.get("imageDigest").map(String::as_str), Some(DIGEST));
+
+        assert!(annotation.validate_base());
+        assert_eq!(annotation.kind, *constants::ANNOTATION_CONTAINER);
+        assert_eq!(annotation.host, gethostname::gethostname().to_str().unwrap());
+        assert_eq!(annotation.hash, config.hash.hash_type);
+        assert!(annotation.is_satisfied)
+    }
+
+    #[test]
+    fn unsatisfied_container_annotation() {
+        let mut config: config::SdkInfo = serde_json::from_slice(crate::CONFIG_BYTES.as_slice()).unwrap();
+        config.container.allow_list = vec![DIGEST.to_string()];
+        let unapproved = ContainerRuntime {
+            in_container: true,
+            container_id: None,
+            image_digest: Some(format!("sha256:{}", "f".repeat(64))),
+        };
+
+        let mut container_annotator = ContainerAnnotator::with_runtime(&config, unapproved).unwrap();
+        assert!(!container_annotator.annotate("Some random data".as_bytes()).unwrap().is_satisfied);
+
+        let mut container_annotator = ContainerAnnotator::with_runtime(&config, ContainerRuntime::default()).unwrap();
+        assert!(!container_annotator.annotate("Some random data".as_bytes()).unwrap().is_satisfied);
+    }
+}
@@ -0,0 +1,203 @@
+use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use crate::annotations::{Annotation, Annotator, PayloadAnnotator, constants};
+use crate::config;
+use alvarium_annotator::{serialise_and_sign, HashProvider};
+use crate::factories::{new_hash_provider, new_host_identity, new_payload_extractor, new_signature_provider};
+use crate::providers::payload_provider::{Payload, PayloadExtractorChain};
+use crate::providers::hash_provider::HashProviderWrapper;
+use crate::providers::sign_provider::SignatureProviderWrap;
+use crate::errors::{Error, Result};
+use log::warn;
+
+#[cfg(target_os = "linux")]
+const SELF_EXE_PATH: &str = "/proc/self/exe";
+#[cfg(target_os = "linux")]
+const SELF_MAPS_PATH: &str = "/proc/self/maps";
+
+/// Attests the running build. The executable, and unless disabled every shared library mapped
+/// into the process, are hashed with the configured hash type and must all appear in the
+/// allow-list for the annotation to be satisfied
+pub struct ExecutableAnnotator {
+    hash: constants::HashType,
+    kind: constants::AnnotationType,
+    hasher: HashProviderWrapper,
+    sign: SignatureProviderWrap,
+    host: Arc<str>,
+    payload: PayloadExtractorChain,
+    allow_list: HashSet<String>,
+    libraries: bool,
+    // Digests by path, files are only hashed the first time they are seen
+    digests: HashMap<PathBuf, String>,
+}
+
+impl ExecutableAnnotator {
+    pub fn new(cfg: &config::SdkInfo) -> Result<impl PayloadAnnotator> {
+        let mut allow_list: HashSet<String> = cfg.executable.allow_list.iter().cloned().collect();
+        if let Some(path) = &cfg.executable.allow_list_path {
+            let file = std::fs::read_to_string(path).map_err(|_| Error::IncorrectConfig)?;
+            allow_list.extend(
+                file.lines()
+                    .map(str::trim)
+                    .filter(|digest| !digest.is_empty() && !digest.starts_with('#'))
+                    .map(str::to_string)
+            );
+        }
+
+        Ok(ExecutableAnnotator {
+            hash: cfg.hash.hash_type.clone(),
+            hasher: new_hash_provider(&cfg.hash.hash_type)?,
+            kind: constants::ANNOTATION_EXECUTABLE.clone(),
+            sign: new_signature_provider(&cfg.signature)?,
+            host: new_host_identity(&cfg.host)?,
+            payload: new_payload_extractor(&cfg.payload),
+            allow_list,
+            libraries: cfg.executable.libraries,
+            digests: HashMap::new(),
+        })
+    }
+
+    fn digest(&mut self, path: &Path) -> Result<Option<String>> {
+        if let Some(digest) = self.digests.get(path) {
+            return Ok(Some(digest.clone()))
+        }
+
+        let contents = match std::fs::read(path) {
+            Ok(contents) => contents,
+            Err(e) => {
+                warn!("Could not read {} for attestation: {}", path.display(), e);
+                return Ok(None)
+            }
+        };
+        let digest = self.hasher.derive(&contents);
+        self.digests.insert(path.to_path_buf(), digest.clone());
+        Ok(Some(digest))
+    }
+
+    /// Digests of the executable followed by those of its loaded libraries. `None` if any of
+    /// them could not be read
+    #[cfg(target_os = "linux")]
+    fn build_digests(&mut self) -> Result<Option<Vec<String>>> {
+        let mut paths = vec![PathBuf::from(SELF_EXE_PATH)];
+        if self.libraries {
+            paths.extend(loaded_libraries());
+        }
+
+        let mut digests = Vec::with_capacity(paths.len());
+        for path in paths {
+            match self.digest(&path)? {
+                Some(digest) => digests.push(digest),
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(digests))
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn build_digests(&mut self) -> Result<Option<Vec<String>>> {
+        Ok(None)
+    }
+
+    fn check_build(&mut self) -> Result<bool> {
+        Ok(match self.build_digests()? {
+            Some(digests) => digests.iter().all(|digest| self.allow_list.contains(digest)),
+            None => false,
+        })
+    }
+}
+
+// Shared objects backing file mappings of this process
+#[cfg(target_os = "linux")]
+fn loaded_libraries() -> std::collections::BTreeSet<PathBuf> {
+    let maps = std::fs::read_to_string(SELF_MAPS_PATH).unwrap_or_default();
+    let exe = std::fs::read_link(SELF_EXE_PATH).ok();
+    maps.lines()
+        // address perms offset dev inode pathname
+        .filter_map(|line| line.split_whitespace().nth(5))
+        .filter(|path| path.starts_with('/') && is_shared_object(path))
+        .map(PathBuf::from)
+        .filter(|path| Some(path) != exe.as_ref())
+        .collect()
+}
+
+#[cfg(target_os = "linux")]
+fn is_shared_object(path: &str) -> bool {
+    let name = path.rsplit('/').next().unwrap_or_default();
+    name.ends_with(".so") || name.contains(".so.")
+}
+
+impl Annotator for ExecutableAnnotator {
+    type Error = crate::errors::Error;
+    fn annotate(&mut self, data: &[u8]) -> Result<Annotation> {
+        let payload = self.payload.extract(data);
+        self.annotate_payload(data, &payload)
+    }
+}
+
+impl PayloadAnnotator for ExecutableAnnotator {
+    fn kind(&self) -> &constants::AnnotationType {
+        &self.kind
+    }
+
+    fn annotate_payload(&mut self, data: &[u8], payload: &Payload) -> Result<Annotation> {
+        let mut annotation = self.annotate_unsigned(data, payload)?;
+        let signature = serialise_and_sign(&self.sign, &annotation)?;
+        annotation.with_signature(&signature);
+        Ok(annotation)
+    }
+
+    fn annotate_unsigned(&mut self, _data: &[u8], payload: &Payload) -> Result<Annotation> {
+        let key = self.hasher.derive(&payload.content);
+        let is_satisfied = self.check_build()?;
+        Ok(Annotation::new(&key, self.hash.clone(), &self.host, self.kind.clone(), is_satisfied))
+    }
+}
+
+
+#[cfg(test)]
+mod executable_tests {
+    use alvarium_annotator::derive_hash;
+    use crate::config;
+    use crate::annotations::{Annotator, ExecutableAnnotator, constants};
+    use crate::factories::new_hash_provider;
+
+    #[test]
+    fn unsatisfied_executable_annotation() {
+        let config: config::SdkInfo = serde_json::from_slice(crate::CONFIG_BYTES.as_slice()).unwrap();
+        let mut executable_annotator = ExecutableAnnotator::new(&config).unwrap();
+        let annotation = executable_annotator.annotate("Some random data".as_bytes()).unwrap();
+
+        assert!(annotation.validate_base());
+        assert_eq!(annotation.kind, *constants::ANNOTATION_EXECUTABLE);
+        assert_eq!(annotation.host, gethostname::gethostname().to_str().unwrap());
+        assert_eq!(annotation.hash, config.hash.hash_type);
+        assert!(!annotation.is_satisfied)
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn make_executable_annotation() {
+        let mut config: config::SdkInfo = serde_json::from_slice(crate::CONFIG_BYTES.as_slice()).unwrap();
+        let digest = |path: &std::path::Path| {
+            derive_hash(new_hash_provider(&config.hash.hash_type).unwrap(), &std::fs::read(path).unwrap())
+        };
+        let exe_digest = digest(std::path::Path::new(super::SELF_EXE_PATH));
+        let library_digests: Vec<String> = super::loaded_libraries().iter().map(|path| digest(path)).collect();
+
+        config.executable.allow_list = vec![exe_digest];
+        config.executable.libraries = false;
+        let mut executable_annotator = ExecutableAnnotator::new(&config).unwrap();
+        assert!(executable_annotator.annotate("Some random data".as_bytes()).unwrap().is_satisfied);
+
+        // Approving the executable alone is not enough while libraries are checked
+        config.executable.libraries = true;
+        let mut executable_annotator = ExecutableAnnotator::new(&config).unwrap();
+        let annotation = executable_annotator.annotate("Some random data".as_bytes()).unwrap();
+        assert_eq!(annotation.is_satisfied, library_digests.is_empty());
+
+        config.executable.allow_list.extend(library_digests);
+        let mut executable_annotator = ExecutableAnnotator::new(&config).unwrap();
+        assert!(executable_annotator.annotate("Some random data".as_bytes()).unwrap().is_satisfied);
+    }
+}
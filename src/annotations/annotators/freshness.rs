@@ -0,0 +1,363 @@
+use std::sync::Arc;
+use std::collections::{BTreeSet, HashMap};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use chrono::{DateTime, TimeZone, Utc};
+use serde_json::Value;
+use crate::annotations::{Annotation, Annotator, PayloadAnnotator, constants};
+use crate::config;
+use alvarium_annotator::{serialise_and_sign, HashProvider};
+use crate::factories::{new_hash_provider, new_host_identity, new_payload_extractor, new_signature_provider};
+use crate::providers::payload_provider::{Payload, PayloadExtractorChain};
+use crate::providers::hash_provider::HashProviderWrapper;
+use crate::providers::sign_provider::SignatureProviderWrap;
+use crate::errors::{Error, Result};
+
+// Integer timestamps above this are taken to be in milliseconds rather than seconds
+const MILLIS_THRESHOLD: i64 = 100_000_000_000;
+// Expired entries a cache file may hold beyond twice its live entries before it is compacted
+const COMPACT_SLACK: usize = 1024;
+
+/// Nonces seen within the freshness window. A nonce is remembered until the timestamp it came
+/// with falls out of the window, after which a replay fails the timestamp check anyway. At most
+/// `capacity` nonces are held, while it is full new nonces are refused, and a capacity of 0 turns
+/// replay detection off. When given a path, the cache is loaded from it on creation and each
+/// nonce is appended to it as it is recorded, so it survives restarts. The file is rewritten
+/// without its expired entries once they outnumber the live ones
+pub struct ReplayCache {
+    capacity: usize,
+    window: chrono::Duration,
+    path: Option<PathBuf>,
+    file: Option<File>,
+    // Entries in the file, live or expired
+    written: usize,
+    expiries: BTreeSet<(i64, String)>,
+    seen: HashMap<String, i64>,
+}
+
+impl ReplayCache {
+    pub fn new(capacity: usize, window: chrono::Duration, path: Option<&str>) -> Result<Self> {
+        let mut cache = ReplayCache {
+            capacity,
+            window,
+            path: path.map(PathBuf::from),
+            file: None,
+            written: 0,
+            expiries: BTreeSet::new(),
+            seen: HashMap::new(),
+        };
+
+        if let Some(path) = &cache.path {
+            match std::fs::read_to_string(path) {
+                // A line that does not parse was cut short by a crash, and is skipped
+                Ok(contents) => contents.lines()
+                    .filter_map(parse_entry)
+                    .for_each(|(expiry, nonce)| cache.remember(nonce, expiry)),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {},
+                Err(e) => return Err(Error::ReplayCacheError(e)),
+            }
+            cache.evict(Utc::now().timestamp());
+            cache.compact()?;
+        }
+        Ok(cache)
+    }
+
+    pub fn contains(&self, nonce: &str) -> bool {
+        self.seen.contains_key(nonce)
+    }
+
+    /// Records a nonce sent with `timestamp`, returning false if it has already been seen or the
+    /// cache is full
+    pub fn insert(&mut self, nonce: &str, timestamp: DateTime<Utc>) -> Result<bool> {
+        if self.capacity == 0 {
+            return Ok(true)
+        }
+
+        self.evict(Utc::now().timestamp());
+        if self.contains(nonce) || self.seen.len() >= self.capacity {
+            return Ok(false)
+        }
+
+        let expiry = (timestamp + self.window).timestamp();
+        self.remember(nonce.to_string(), expiry);
+        self.persist(nonce, expiry)?;
+        Ok(true)
+    }
+
+    fn remember(&mut self, nonce: String, expiry: i64) {
+        if let Some(previous) = self.seen.insert(nonce.clone(), expiry) {
+            self.expiries.remove(&(previous, nonce.clone()));
+        }
+        self.expiries.insert((expiry, nonce));
+    }
+
+    fn evict(&mut self, now: i64) {
+        while let Some((expiry, nonce)) = self.expiries.iter().next().cloned() {
+            if expiry > now {
+                break
+            }
+            self.expiries.remove(&(expiry, nonce.clone()));
+            self.seen.remove(&nonce);
+        }
+    }
+
+    fn persist(&mut self, nonce: &str, expiry: i64) -> Result<()> {
+        if self.written >= 2 * self.seen.len() + COMPACT_SLACK {
+            return self.compact()
+        }
+
+        if let Some(file) = &mut self.file {
+            file.write_all(format_entry(expiry, nonce).as_bytes()).map_err(Error::ReplayCacheError)?;
+            self.written += 1;
+        }
+        Ok(())
+    }
+
+    // Rewrites the file with the live entries only, to a sibling file that is then renamed over
+    // it so a crash mid-write cannot lose the cache, and reopens it for appending
+    fn compact(&mut self) -> Result<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+
+        let tmp = path.with_extension("tmp");
+        let mut file = File::create(&tmp).map_err(Error::ReplayCacheError)?;
+        for (expiry, nonce) in &self.expiries {
+            file.write_all(format_entry(*expiry, nonce).as_bytes()).map_err(Error::ReplayCacheError)?;
+        }
+        file.sync_all().map_err(Error::ReplayCacheError)?;
+        std::fs::rename(&tmp, path).map_err(Error::ReplayCacheError)?;
+
+        let file = OpenOptions::new().append(true).open(path).map_err(Error::ReplayCacheError)?;
+        self.file = Some(file);
+        self.written = self.expiries.len();
+        Ok(())
+    }
+}
+
+// One entry per line, the expiry as a unix timestamp and the nonce hex encoded, as a nonce may
+// hold any character including line breaks
+fn format_entry(expiry: i64, nonce: &str) -> String {
+    format!("{} {}\n", expiry, hex::encode(nonce))
+}
+
+fn parse_entry(line: &str) -> Option<(i64, String)> {
+    let (expiry, nonce) = line.split_once(' ')?;
+    let nonce = String::from_utf8(hex::decode(nonce).ok()?).ok()?;
+    Some((expiry.parse().ok()?, nonce))
+}
+
+pub struct FreshnessAnnotator {
+    hash: constants::HashType,
+    kind: constants::AnnotationType,
+    hasher: HashProviderWrapper,
+    sign: SignatureProviderWrap,
+    host: Arc<str>,
+    payload: PayloadExtractorChain,
+    timestamp_field: String,
+    nonce_field: String,
+    window: chrono::Duration,
+    cache: ReplayCache,
+}
+
+impl FreshnessAnnotator {
+    pub fn new(cfg: &config::SdkInfo) -> Result<impl PayloadAnnotator> {
+        let freshness = &cfg.freshness;
+        Ok(FreshnessAnnotator {
+            hash: cfg.hash.hash_type.clone(),
+            hasher: new_hash_provider(&cfg.hash.hash_type)?,
+            kind: constants::ANNOTATION_FRESHNESS.clone(),
+            sign: new_signature_provider(&cfg.signature)?,
+            host: new_host_identity(&cfg.host)?,
+            payload: new_payload_extractor(&cfg.payload),
+            timestamp_field: freshness.timestamp_field.clone(),
+            nonce_field: freshness.nonce_field.clone(),
+            window: chrono::Duration::seconds(freshness.window_seconds as i64),
+            cache: ReplayCache::new(
+                freshness.cache_size,
+                chrono::Duration::seconds(freshness.window_seconds as i64),
+                freshness.cache_path.as_deref()
+            )?,
+        })
+    }
+
+    /// True if the payload timestamp is within the window of local time and its nonce has not
+    /// been seen before. The nonce is only recorded once the timestamp has been accepted
+    fn check_freshness(&mut self, payload: &Payload) -> Result<bool> {
+        let timestamp = match field(payload, &self.timestamp_field).as_ref().and_then(parse_timestamp) {
+            Some(timestamp) => timestamp,
+            None => return Ok(false),
+        };
+        let nonce = match field(payload, &self.nonce_field) {
+            Some(Value::String(nonce)) => nonce,
+            Some(Value::Number(nonce)) => nonce.to_string(),
+            _ => return Ok(false),
+        };
+
+        let skew = (Utc::now() - timestamp).num_seconds().abs();
+        if skew > self.window.num_seconds() {
+            return Ok(false)
+        }
+        self.cache.insert(&nonce, timestamp)
+    }
+}
+
+// Protected envelope headers first, then a top level field of a JSON object content, both of
+// which the payload signature covers when there is one
+fn field(payload: &Payload, name: &str) -> Option<Value> {
+    payload.headers.get(name)
+        .or_else(|| payload.headers.get(&name.to_lowercase()))
+        .map(|value| Value::String(value.clone()))
+        .or_else(|| {
+            serde_json::from_slice::<Value>(&payload.content).ok()?
+                .get(name)
+                .cloned()
+        })
+}
+
+// Accepts RFC 3339 strings and integer unix timestamps in seconds or milliseconds, as numbers or
+// strings
+fn parse_timestamp(value: &Value) -> Option<DateTime<Utc>> {
+    let unix = match value {
+        Value::Number(number) => number.as_i64()?,
+        Value::String(timestamp) => match DateTime::parse_from_rfc3339(timestamp) {
+            Ok(timestamp) => return Some(timestamp.with_timezone(&Utc)),
+            Err(_) => timestamp.parse::<i64>().ok()?,
+        },
+        _ => return None,
+    };
+
+    match unix.abs() > MILLIS_THRESHOLD {
+        true => Utc.timestamp_millis_opt(unix).single(),
+        false => Utc.timestamp_opt(unix, 0).single(),
+    }
+}
+
+impl Annotator for FreshnessAnnotator {
+    type Error = crate::errors::Error;
+    fn annotate(&mut self, data: &[u8]) -> Result<Annotation> {
+        let payload = self.payload.extract(data);
+        self.annotate_payload(data, &payload)
+    }
+}
+
+impl PayloadAnnotator for FreshnessAnnotator {
+    fn kind(&self) -> &constants::AnnotationType {
+        &self.kind
+    }
+
+    fn annotate_payload(&mut self, data: &[u8], payload: &Payload) -> Result<Annotation> {
+        let mut annotation = self.annotate_unsigned(data, payload)?;
+        let signature = serialise_and_sign(&self.sign, &annotation)?;
+        annotation.with_signature(&signature);
+        Ok(annotation)
+    }
+
+    fn annotate_unsigned(&mut self, _data: &[u8], payload: &Payload) -> Result<Annotation> {
+        let key = self.hasher.derive(&payload.content);
+        let is_satisfied = self.check_freshness(payload)?;
+        Ok(Annotation::new(&key, self.hash.clone(), &self.host, self.kind.clone(), is_satisfied))
+    }
+}
+
+
+#[cfg(test)]
+mod freshness_tests {
+    use crate::config;
+    use crate::annotations::{Annotator, FreshnessAnnotator, ReplayCache, constants};
+
+    fn reading(timestamp: i64, nonce: &str) -> Vec<u8> {
+        format!(r#"{{"temperature":21.5,"timestamp":{},"nonce":"{}"}}"#, timestamp, nonce).into_bytes()
+    }
+
+    #[test]
+    fn make_freshness_annotation() {
+        let config: config::SdkInfo = serde_json::from_slice(crate::CONFIG_BYTES.as_slice()).unwrap();
+        let mut freshness_annotator = FreshnessAnnotator::new(&config).unwrap();
+
+        let data = reading(chrono::Utc::now().timestamp(), "fresh-nonce");
+        let annotation = freshness_annotator.annotate(&data).unwrap();
+
+        assert!(annotation.validate_base());
+        assert_eq!(annotation.kind, *constants::ANNOTATION_FRESHNESS);
+        assert_eq!(annotation.host, gethostname::gethostname().to_str().unwrap());
+        assert_eq!(annotation.hash, config.hash.hash_type);
+        assert!(annotation.is_satisfied)
+    }
+
+    #[test]
+    fn unsatisfied_freshness_annotation() {
+        let config: config::SdkInfo = serde_json::from_slice(crate::CONFIG_BYTES.as_slice()).unwrap();
+        let mut freshness_annotator = FreshnessAnnotator::new(&config).unwrap();
+
+        let now = chrono::Utc::now().timestamp();
+        let stale = reading(now - 3600, "stale-nonce");
+        let replayed = reading(now, "replayed-nonce");
+
+        assert!(!freshness_annotator.annotate(&stale).unwrap().is_satisfied);
+        assert!(freshness_annotator.annotate(&replayed).unwrap().is_satisfied);
+        assert!(!freshness_annotator.annotate(&replayed).unwrap().is_satisfied);
+        assert!(!freshness_annotator.annotate("Some random data".as_bytes()).unwrap().is_satisfied);
+    }
+
+    #[test]
+    fn persisted_replay_cache() {
+        let path = std::env::temp_dir().join(format!("alvarium_replay_{}", ulid::Ulid::new()));
+        let path = path.to_str().unwrap();
+        let window = chrono::Duration::seconds(60);
+        let now = chrono::Utc::now();
+
+        let mut cache = ReplayCache::new(10, window, Some(path)).unwrap();
+        assert!(cache.insert("a", now).unwrap());
+        assert!(cache.insert("b\nc", now).unwrap());
+        assert!(!cache.insert("a", now).unwrap());
+        // Out of the window already, so forgotten by the next insert
+        assert!(cache.insert("old", now - window * 2).unwrap());
+        assert!(cache.insert("d", now).unwrap());
+        assert!(!cache.contains("old"));
+
+        // Reloaded from disk, a nonce holding a line break is still one nonce
+        let cache = ReplayCache::new(10, window, Some(path)).unwrap();
+        assert!(cache.contains("a") && cache.contains("b\nc") && cache.contains("d"));
+        assert!(!cache.contains("b") && !cache.contains("c") && !cache.contains("old"));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn replay_cache_bounded() {
+        let window = chrono::Duration::seconds(60);
+        let now = chrono::Utc::now();
+
+        // Full of nonces still in the window, new ones are refused rather than old ones forgotten
+        let mut cache = ReplayCache::new(2, window, None).unwrap();
+        assert!(cache.insert("a", now).unwrap());
+        assert!(cache.insert("b", now).unwrap());
+        assert!(!cache.insert("c", now).unwrap());
+        assert!(cache.contains("a"));
+
+        // Room is made as nonces leave the window
+        let mut cache = ReplayCache::new(2, window, None).unwrap();
+        assert!(cache.insert("a", now - window * 2).unwrap());
+        assert!(cache.insert("b", now).unwrap());
+        assert!(cache.insert("c", now).unwrap());
+        assert!(!cache.contains("a"));
+    }
+
+    #[test]
+    fn replay_cache_compacted() {
+        let path = std::env::temp_dir().join(format!("alvarium_replay_{}", ulid::Ulid::new()));
+        let path = path.to_str().unwrap();
+        let window = chrono::Duration::seconds(60);
+        let expired = chrono::Utc::now() - window * 2;
+
+        let mut cache = ReplayCache::new(10, window, Some(path)).unwrap();
+        for i in 0..(3 * super::COMPACT_SLACK) {
+            assert!(cache.insert(&i.to_string(), expired).unwrap());
+        }
+        let lines = std::fs::read_to_string(path).unwrap().lines().count();
+        assert!(lines <= super::COMPACT_SLACK + 2);
+        std::fs::remove_file(path).unwrap();
+    }
+}
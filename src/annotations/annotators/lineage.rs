@@ -0,0 +1,125 @@
+use std::sync::Arc;
+use serde::{Serialize, Deserialize};
+use alvarium_annotator::{HashProvider, SignProvider};
+use crate::annotations::{Annotation, Stamp, StampedAnnotation, constants};
+use crate::config;
+use crate::factories::{new_hash_provider, new_host_identity, new_payload_extractor, new_signature_provider};
+use crate::providers::payload_provider::PayloadExtractorChain;
+use crate::providers::hash_provider::HashProviderWrapper;
+use crate::providers::sign_provider::SignatureProviderWrap;
+use crate::errors::Result;
+
+/// Links derived data back to the data it was derived from. The key of the wrapped annotation is
+/// the key of the derived (child) data, `parents` holds the keys of every input, so a merge of
+/// several inputs is recorded as a single lineage annotation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LineageAnnotation {
+    #[serde(flatten)]
+    pub annotation: Annotation,
+    pub parents: Vec<String>,
+    #[serde(flatten)]
+    pub stamp: Stamp,
+}
+
+/// The annotations of a mutate or merge, published as one message with the lineage linking them
+/// so that consumers never receive one without the other. `items` keeps the shape of an
+/// annotation list
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LineageAnnotationList {
+    pub items: Vec<StampedAnnotation>,
+    pub lineage: LineageAnnotation,
+}
+
+pub struct LineageAnnotator {
+    hash: constants::HashType,
+    kind: constants::AnnotationType,
+    hasher: HashProviderWrapper,
+    sign: SignatureProviderWrap,
+    host: Arc<str>,
+    payload: PayloadExtractorChain,
+}
+
+impl LineageAnnotator {
+    pub fn new(cfg: &config::SdkInfo) -> Result<Self> {
+        Ok(LineageAnnotator {
+            hash: cfg.hash.hash_type.clone(),
+            hasher: new_hash_provider(&cfg.hash.hash_type)?,
+            kind: constants::ANNOTATION_LINEAGE.clone(),
+            sign: new_signature_provider(&cfg.signature)?,
+            host: new_host_identity(&cfg.host)?,
+            payload: new_payload_extractor(&cfg.payload),
+        })
+    }
+
+    pub fn annotate_lineage(&mut self, parents: &[&[u8]], child: &[u8], stamp: Stamp) -> Result<LineageAnnotation> {
+        let parents = parents.iter()
+            .map(|parent| self.derive_key(parent))
+            .collect::<Vec<String>>();
+        let key = self.derive_key(child);
+
+        let mut lineage = LineageAnnotation {
+            annotation: Annotation::new(&key, self.hash.clone(), &self.host, self.kind.clone(), true),
+            parents,
+            stamp,
+        };
+        // Signed over the parents and stamp as well as the base annotation fields
+        let signature = self.sign.sign(&serde_json::to_vec(&lineage)?)?;
+        lineage.annotation.with_signature(&signature);
+        Ok(lineage)
+    }
+
+    fn derive_key(&self, data: &[u8]) -> String {
+        self.hasher.derive(&self.payload.extract(data).content)
+    }
+}
+
+
+#[cfg(test)]
+mod lineage_tests {
+    use alvarium_annotator::{derive_hash, SignProvider};
+    use crate::config;
+    use crate::annotations::{constants, LineageAnnotator};
+    use crate::factories::{new_hash_provider, new_signature_provider};
+
+    #[test]
+    fn make_lineage_annotation() {
+        let config: config::SdkInfo = serde_json::from_slice(crate::CONFIG_BYTES.as_slice()).unwrap();
+
+        let old = "Some old state of the data before mutation".as_bytes();
+        let new = "Some new state of the data after mutation".as_bytes();
+
+        let mut lineage_annotator = LineageAnnotator::new(&config).unwrap();
+        let mut lineage = lineage_annotator.annotate_lineage(&[old], new, config.stamp()).unwrap();
+
+        let hasher = || new_hash_provider(&config.hash.hash_type).unwrap();
+        assert_eq!(lineage.annotation.kind, *constants::ANNOTATION_LINEAGE);
+        assert_eq!(lineage.annotation.key, derive_hash(hasher(), new));
+        assert_eq!(lineage.parents, vec![derive_hash(hasher(), old)]);
+        assert!(lineage.annotation.is_satisfied);
+
+        // The signature covers the parent keys
+        let signature = hex::decode(&lineage.annotation.signature).unwrap();
+        lineage.annotation.with_signature("");
+        let sign = new_signature_provider(&config.signature).unwrap();
+        assert!(sign.verify(&serde_json::to_vec(&lineage).unwrap(), &signature).unwrap());
+
+        lineage.parents.push("Some other parent".to_string());
+        assert!(!sign.verify(&serde_json::to_vec(&lineage).unwrap(), &signature).unwrap());
+    }
+
+    #[test]
+    fn merged_lineage_annotation() {
+        let config: config::SdkInfo = serde_json::from_slice(crate::CONFIG_BYTES.as_slice()).unwrap();
+
+        let first = "The first input of a merge".as_bytes();
+        let second = "The second input of a merge".as_bytes();
+        let merged = "The merged output".as_bytes();
+
+        let mut lineage_annotator = LineageAnnotator::new(&config).unwrap();
+        let lineage = lineage_annotator.annotate_lineage(&[first, second], merged, config.stamp()).unwrap();
+
+        let hasher = || new_hash_provider(&config.hash.hash_type).unwrap();
+        assert_eq!(lineage.annotation.key, derive_hash(hasher(), merged));
+        assert_eq!(lineage.parents, vec![derive_hash(hasher(), first), derive_hash(hasher(), second)]);
+    }
+}
@@ -1,11 +1,23 @@
+mod container;
+mod executable;
+mod freshness;
+mod lineage;
 mod pki;
+mod schema;
 mod source;
 mod tls;
+mod tls_policy;
 mod tpm;
 
+pub use container::*;
+pub use executable::*;
+pub use freshness::*;
+pub use lineage::*;
 pub use pki::*;
+pub use schema::*;
 pub use source::*;
 pub use tls::*;
+pub use tls_policy::*;
 pub use tpm::*;
 
 
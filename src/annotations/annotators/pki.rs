@@ -1,51 +1,84 @@
+use std::sync::Arc;
 use crate::annotations::{
     Annotation,
     Annotator,
+    PayloadAnnotator,
     constants,
 };
-use crate::{config::{self, Signable}};
-use crate::providers::sign_provider::SignatureProviderWrap;
-use alvarium_annotator::{derive_hash, serialise_and_sign};
-use crate::factories::{new_hash_provider, new_signature_provider};
-use crate::errors::{Result, Error};
+use crate::config;
+use crate::providers::hash_provider::HashProviderWrapper;
+use crate::providers::sign_provider::{Keyring, SignatureProviderWrap};
+use alvarium_annotator::{serialise_and_sign, HashProvider};
+use crate::factories::{new_hash_provider, new_host_identity, new_keyring, new_payload_extractor, new_signature_provider};
+use crate::providers::payload_provider::{Payload, PayloadExtractorChain, PayloadSignature};
+use crate::errors::Result;
 
 pub struct PkiAnnotator {
     hash: constants::HashType,
     kind: constants::AnnotationType,
+    hasher: HashProviderWrapper,
     sign: SignatureProviderWrap,
+    host: Arc<str>,
+    payload: PayloadExtractorChain,
+    keyring: Keyring,
 }
 
 impl PkiAnnotator {
-    pub fn new(cfg: &config::SdkInfo) -> Result<impl Annotator<Error = Error>> {
+    pub fn new(cfg: &config::SdkInfo) -> Result<impl PayloadAnnotator> {
         Ok(PkiAnnotator {
             hash: cfg.hash.hash_type.clone(),
+            hasher: new_hash_provider(&cfg.hash.hash_type)?,
             kind: constants::ANNOTATION_PKI.clone(),
             sign: new_signature_provider(&cfg.signature)?,
+            host: new_host_identity(&cfg.host)?,
+            payload: new_payload_extractor(&cfg.payload),
+            keyring: new_keyring(&cfg.signature)?,
         })
     }
+
+    // A missing or malformed signature leaves the annotation unsatisfied rather than failing it
+    fn verify_signature(&self, signature: &PayloadSignature) -> Result<bool> {
+        if signature.signature.is_empty() {
+            return Ok(false)
+        }
+
+        self.keyring.verify(
+            signature.key_id.as_deref(),
+            &self.sign,
+            &signature.algorithm,
+            &signature.signing_input,
+            &signature.signature
+        )
+    }
 }
 
 impl Annotator for PkiAnnotator {
     type Error = crate::errors::Error;
     fn annotate(&mut self, data: &[u8]) -> Result<Annotation> {
-        let hasher = new_hash_provider(&self.hash)?;
-        let signable: std::result::Result<Signable, serde_json::Error> = serde_json::from_slice(data);
-        let (verified, key) = match signable {
-            Ok(signable) => {
-                let key = derive_hash(hasher, signable.seed.as_bytes());
-                (signable.verify_signature(&self.sign)?, key)
-            },
-            Err(_) => (false, derive_hash(hasher, data)),
+        let payload = self.payload.extract(data);
+        self.annotate_payload(data, &payload)
+    }
+}
+
+impl PayloadAnnotator for PkiAnnotator {
+    fn kind(&self) -> &constants::AnnotationType {
+        &self.kind
+    }
+
+    fn annotate_payload(&mut self, data: &[u8], payload: &Payload) -> Result<Annotation> {
+        let mut annotation = self.annotate_unsigned(data, payload)?;
+        let signature = serialise_and_sign(&self.sign, &annotation)?;
+        annotation.with_signature(&signature);
+        Ok(annotation)
+    }
+
+    fn annotate_unsigned(&mut self, _data: &[u8], payload: &Payload) -> Result<Annotation> {
+        let key = self.hasher.derive(&payload.content);
+        let verified = match &payload.signature {
+            Some(signature) => self.verify_signature(signature)?,
+            None => false,
         };
-        match gethostname::gethostname().to_str() {
-            Some(host) => {
-                let mut annotation = Annotation::new(&key, self.hash.clone(), host, self.kind.clone(), verified);
-                let signature = serialise_and_sign(&self.sign, &annotation)?;
-                annotation.with_signature(&signature);
-                Ok(annotation)
-            },
-            None => Err(Error::NoHostName)
-        }
+        Ok(Annotation::new(&key, self.hash.clone(), &self.host, self.kind.clone(), verified))
     }
 }
 
@@ -56,6 +89,11 @@ mod pki_tests {
     use crate::{config, providers::sign_provider::get_priv_key};
     use crate::annotations::{Annotator, PkiAnnotator, constants};
     use crate::config::Signable;
+    use crate::factories::new_hash_provider;
+    use alvarium_annotator::derive_hash;
+    use coset::{iana, CoseSign1Builder, HeaderBuilder, TaggedCborSerializable};
+    use p256::ecdsa::{signature::Signer, SigningKey};
+    use crate::providers::sign_provider::{KeyEntry, KeyType};
 
     #[test]
     fn valid_and_invalid_pki_annotator() {
@@ -71,13 +109,13 @@ mod pki_tests {
         let serialised = serde_json::to_vec(&signable).unwrap();
 
         let mut pki_annotator_1 = PkiAnnotator::new(&config).unwrap();
-        let mut pki_annotator_2 = PkiAnnotator::new(&config2).unwrap();
+        // The hash provider is resolved when the annotator is built
+        let invalid_annotator = PkiAnnotator::new(&config2);
 
         let valid_annotation = pki_annotator_1.annotate(&serialised).unwrap();
-        let invalid_annotation = pki_annotator_2.annotate(&serialised);
 
         assert!(valid_annotation.validate_base());
-        assert!(invalid_annotation.is_err());
+        assert!(invalid_annotator.is_err());
     }
 
 
@@ -124,4 +162,85 @@ mod pki_tests {
         assert_eq!(annotation.hash, config.hash.hash_type);
         assert!(!annotation.is_satisfied)
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn malformed_signatures_unsatisfied() {
+        let config: config::SdkInfo = serde_json::from_slice(crate::CONFIG_BYTES.as_slice()).unwrap();
+        let mut pki_annotator = PkiAnnotator::new(&config).unwrap();
+
+        for sig in [String::new(), hex::encode([0u8; 12])] {
+            let signable = Signable::new("Some random data".to_string(), sig);
+            let annotation = pki_annotator.annotate(&signable.to_bytes()).unwrap();
+            assert!(annotation.validate_base());
+            assert!(!annotation.is_satisfied);
+        }
+    }
+
+    #[test]
+    fn make_jws_pki_annotation() {
+        let config: config::SdkInfo = serde_json::from_slice(crate::CONFIG_BYTES.as_slice()).unwrap();
+
+        let priv_key_file = std::fs::read(&config.signature.private_key_info.path).unwrap();
+        let priv_key = get_priv_key(&String::from_utf8(priv_key_file).unwrap()).unwrap();
+
+        let header = base64::encode_config(br#"{"alg":"EdDSA"}"#, base64::URL_SAFE_NO_PAD);
+        let payload = base64::encode_config(b"Some random data", base64::URL_SAFE_NO_PAD);
+        let signing_input = format!("{}.{}", header, payload);
+        let sig = priv_key.sign(signing_input.as_bytes());
+        let jws = format!("{}.{}", signing_input, base64::encode_config(sig.to_bytes(), base64::URL_SAFE_NO_PAD));
+
+        let mut pki_annotator = PkiAnnotator::new(&config).unwrap();
+        let annotation = pki_annotator.annotate(jws.as_bytes()).unwrap();
+
+        let hasher = new_hash_provider(&config.hash.hash_type).unwrap();
+        assert!(annotation.validate_base());
+        assert_eq!(annotation.key, derive_hash(hasher, b"Some random data"));
+        assert!(annotation.is_satisfied)
+    }
+
+    #[test]
+    fn make_cose_pki_annotation_with_keyring() {
+        let mut config: config::SdkInfo = serde_json::from_slice(crate::CONFIG_BYTES.as_slice()).unwrap();
+
+        let signing_key = SigningKey::from_slice(&[7u8; 32]).unwrap();
+        let entries = vec![KeyEntry {
+            id: "device-7".to_string(),
+            key_type: KeyType::P256,
+            public: hex::encode(signing_key.verifying_key().to_encoded_point(true).as_bytes()),
+        }];
+        let keyring_path = std::env::temp_dir().join("alvarium_pki_keyring.json");
+        std::fs::write(&keyring_path, serde_json::to_vec(&entries).unwrap()).unwrap();
+        config.signature.keyring = Some(keyring_path.to_str().unwrap().to_string());
+
+        let protected = HeaderBuilder::new()
+            .algorithm(iana::Algorithm::ES256)
+            .key_id(b"device-7".to_vec())
+            .build();
+        let sign1 = CoseSign1Builder::new()
+            .protected(protected)
+            .payload(b"Some random data".to_vec())
+            .create_signature(&[], |tbs| {
+                let signature: p256::ecdsa::Signature = signing_key.sign(tbs);
+                signature.to_bytes().to_vec()
+            })
+            .build();
+
+        let mut pki_annotator = PkiAnnotator::new(&config).unwrap();
+        let annotation = pki_annotator.annotate(&sign1.to_tagged_vec().unwrap()).unwrap();
+        assert!(annotation.validate_base());
+        assert!(annotation.is_satisfied);
+
+        // Same envelope signed by a key the keyring does not hold
+        let untrusted = SigningKey::from_slice(&[8u8; 32]).unwrap();
+        let sign1 = CoseSign1Builder::new()
+            .protected(sign1.protected.header.clone())
+            .payload(b"Some random data".to_vec())
+            .create_signature(&[], |tbs| {
+                let signature: p256::ecdsa::Signature = untrusted.sign(tbs);
+                signature.to_bytes().to_vec()
+            })
+            .build();
+        let annotation = pki_annotator.annotate(&sign1.to_tagged_vec().unwrap()).unwrap();
+        assert!(!annotation.is_satisfied);
+    }
+}
@@ -0,0 +1,148 @@
+use std::sync::Arc;
+use std::collections::HashMap;
+use jsonschema::JSONSchema;
+use serde_json::Value;
+use crate::annotations::{Annotation, Annotator, PayloadAnnotator, constants};
+use crate::config;
+use alvarium_annotator::{serialise_and_sign, HashProvider};
+use crate::factories::{new_hash_provider, new_host_identity, new_payload_extractor, new_signature_provider};
+use crate::providers::payload_provider::{Payload, PayloadExtractorChain};
+use crate::providers::hash_provider::HashProviderWrapper;
+use crate::providers::sign_provider::SignatureProviderWrap;
+use crate::errors::{Error, Result};
+
+pub struct SchemaAnnotator {
+    hash: constants::HashType,
+    kind: constants::AnnotationType,
+    hasher: HashProviderWrapper,
+    sign: SignatureProviderWrap,
+    host: Arc<str>,
+    payload: PayloadExtractorChain,
+    default_schema: Option<JSONSchema>,
+    message_type_field: Option<String>,
+    schemas: HashMap<String, JSONSchema>,
+}
+
+impl SchemaAnnotator {
+    pub fn new(cfg: &config::SdkInfo) -> Result<impl PayloadAnnotator> {
+        let schemas = cfg.schema.schemas.iter()
+            .map(|(message_type, path)| Ok((message_type.clone(), load_schema(path)?)))
+            .collect::<Result<HashMap<String, JSONSchema>>>()?;
+
+        Ok(SchemaAnnotator {
+            hash: cfg.hash.hash_type.clone(),
+            hasher: new_hash_provider(&cfg.hash.hash_type)?,
+            kind: constants::ANNOTATION_SCHEMA.clone(),
+            sign: new_signature_provider(&cfg.signature)?,
+            host: new_host_identity(&cfg.host)?,
+            payload: new_payload_extractor(&cfg.payload),
+            default_schema: cfg.schema.path.as_deref().map(load_schema).transpose()?,
+            message_type_field: cfg.schema.message_type_field.clone(),
+            schemas,
+        })
+    }
+
+    /// True only if the content is JSON and conforms to the schema selected for it. Content with
+    /// no applicable schema does not conform
+    fn conforms(&self, content: &[u8]) -> bool {
+        let instance: Value = match serde_json::from_slice(content) {
+            Ok(instance) => instance,
+            Err(_) => return false,
+        };
+
+        let schema = self.message_type_field.as_ref()
+            .and_then(|field| instance.get(field))
+            .and_then(Value::as_str)
+            .and_then(|message_type| self.schemas.get(message_type))
+            .or(self.default_schema.as_ref());
+
+        match schema {
+            Some(schema) => schema.is_valid(&instance),
+            None => false,
+        }
+    }
+}
+
+fn load_schema(path: &str) -> Result<JSONSchema> {
+    let file = std::fs::read(path).map_err(|e| Error::SchemaError(format!("{}: {}", path, e)))?;
+    let schema: Value = serde_json::from_slice(&file)?;
+    JSONSchema::compile(&schema).map_err(|e| Error::SchemaError(format!("{}: {}", path, e)))
+}
+
+impl Annotator for SchemaAnnotator {
+    type Error = crate::errors::Error;
+    fn annotate(&mut self, data: &[u8]) -> Result<Annotation> {
+        let payload = self.payload.extract(data);
+        self.annotate_payload(data, &payload)
+    }
+}
+
+impl PayloadAnnotator for SchemaAnnotator {
+    fn kind(&self) -> &constants::AnnotationType {
+        &self.kind
+    }
+
+    fn annotate_payload(&mut self, data: &[u8], payload: &Payload) -> Result<Annotation> {
+        let mut annotation = self.annotate_unsigned(data, payload)?;
+        let signature = serialise_and_sign(&self.sign, &annotation)?;
+        annotation.with_signature(&signature);
+        Ok(annotation)
+    }
+
+    fn annotate_unsigned(&mut self, _data: &[u8], payload: &Payload) -> Result<Annotation> {
+        let key = self.hasher.derive(&payload.content);
+        let is_satisfied = self.conforms(&payload.content);
+        Ok(Annotation::new(&key, self.hash.clone(), &self.host, self.kind.clone(), is_satisfied))
+    }
+}
+
+
+#[cfg(test)]
+mod schema_tests {
+    use crate::config::{self, Signable};
+    use crate::annotations::{Annotator, SchemaAnnotator, constants};
+
+    fn schema_config() -> config::SdkInfo {
+        let mut config: config::SdkInfo = serde_json::from_slice(crate::CONFIG_BYTES.as_slice()).unwrap();
+        config.schema.path = Some("resources/schemas/reading.json".to_string());
+        config.schema.message_type_field = Some("type".to_string());
+        config.schema.schemas.insert("event".to_string(), "resources/schemas/event.json".to_string());
+        config
+    }
+
+    #[test]
+    fn make_schema_annotation() {
+        let config = schema_config();
+        let mut schema_annotator = SchemaAnnotator::new(&config).unwrap();
+
+        let reading = Signable::new(r#"{"temperature":21.5}"#.to_string(), String::new());
+        let annotation = schema_annotator.annotate(&reading.to_bytes()).unwrap();
+
+        assert!(annotation.validate_base());
+        assert_eq!(annotation.kind, *constants::ANNOTATION_SCHEMA);
+        assert_eq!(annotation.host, gethostname::gethostname().to_str().unwrap());
+        assert_eq!(annotation.hash, config.hash.hash_type);
+        assert!(annotation.is_satisfied);
+
+        let event = r#"{"type":"event","name":"door opened"}"#.as_bytes();
+        assert!(schema_annotator.annotate(event).unwrap().is_satisfied);
+    }
+
+    #[test]
+    fn unsatisfied_schema_annotation() {
+        let mut schema_annotator = SchemaAnnotator::new(&schema_config()).unwrap();
+
+        // Event schema selected by the type field, which requires a name
+        let event = r#"{"type":"event","temperature":21.5}"#.as_bytes();
+        assert!(!schema_annotator.annotate(event).unwrap().is_satisfied);
+        assert!(!schema_annotator.annotate(r#"{"temperature":"hot"}"#.as_bytes()).unwrap().is_satisfied);
+        assert!(!schema_annotator.annotate("Some random data".as_bytes()).unwrap().is_satisfied);
+    }
+
+    #[test]
+    fn invalid_schema_file() {
+        let mut config = schema_config();
+        config.schema.path = Some("resources/schemas/missing.json".to_string());
+        assert!(SchemaAnnotator::new(&config).is_err());
+    }
+}
@@ -1,9 +1,10 @@
+use std::sync::Arc;
 #[cfg(feature = "rustls")]
 use std::io::Read;
-use crate::annotations::{Annotation, Annotator, constants};
+use crate::annotations::{Annotation, Annotator, PayloadAnnotator, constants};
 use crate::config;
-use crate::errors::{Result, Error};
-use alvarium_annotator::{derive_hash, serialise_and_sign};
+use crate::errors::Result;
+use alvarium_annotator::{serialise_and_sign, HashProvider};
 
 
 #[cfg(feature = "native-tls")]
@@ -14,14 +15,79 @@ use std::net::TcpStream;
 #[cfg(feature = "native-tls")]
 use std::sync::Mutex;
 use log::info;
-use crate::config::Signable;
-use crate::factories::{new_hash_provider, new_signature_provider};
+#[cfg(feature = "native-tls")]
+use log::warn;
+use crate::factories::{new_hash_provider, new_host_identity, new_payload_extractor, new_signature_provider};
+use crate::providers::payload_provider::{Payload, PayloadExtractorChain};
+use crate::providers::hash_provider::HashProviderWrapper;
 use crate::providers::sign_provider::SignatureProviderWrap;
+use super::{TlsPolicy, TlsSide};
+
+/// Snapshot of a negotiated TLS session. It is taken from the session state alone, so it can be
+/// captured from async streams without the annotator ever reading from or writing to the socket
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TlsSession {
+    pub handshake_complete: bool,
+    /// Wire value of the negotiated protocol version (e.g. 0x0304 for TLS 1.3)
+    pub protocol_version: Option<u16>,
+    /// IANA name of the negotiated cipher suite (e.g. TLS13_AES_256_GCM_SHA384)
+    pub cipher_suite: Option<String>,
+    /// DER encoded peer certificate chain, end entity first
+    pub peer_certificates: Vec<Vec<u8>>,
+    /// Local end of the connection, if known
+    pub side: Option<TlsSide>,
+}
+
+#[cfg(feature = "rustls")]
+impl From<&rustls::CommonState> for TlsSession {
+    fn from(state: &rustls::CommonState) -> Self {
+        TlsSession {
+            handshake_complete: !state.is_handshaking(),
+            protocol_version: state.protocol_version().map(|version| version.get_u16()),
+            cipher_suite: state.negotiated_cipher_suite()
+                .map(|suite| {
+                    let suite = suite.suite();
+                    suite.as_str()
+                        .map(|name| name.to_string())
+                        .unwrap_or_else(|| format!("{:#06x}", suite.get_u16()))
+                }),
+            peer_certificates: state.peer_certificates()
+                .map(|certs| certs.iter().map(|cert| cert.0.clone()).collect())
+                .unwrap_or_default(),
+            side: None,
+        }
+    }
+}
+
+#[cfg(feature = "rustls")]
+impl From<&rustls::ServerConnection> for TlsSession {
+    fn from(conn: &rustls::ServerConnection) -> Self {
+        TlsSession {
+            side: Some(TlsSide::Server),
+            ..TlsSession::from(&**conn)
+        }
+    }
+}
+
+#[cfg(feature = "rustls")]
+impl From<&rustls::ClientConnection> for TlsSession {
+    fn from(conn: &rustls::ClientConnection) -> Self {
+        TlsSession {
+            side: Some(TlsSide::Client),
+            ..TlsSession::from(&**conn)
+        }
+    }
+}
 
 pub struct TlsAnnotator{
     hash: constants::HashType,
     kind: constants::AnnotationType,
+    hasher: HashProviderWrapper,
     sign: SignatureProviderWrap,
+    host: Arc<str>,
+    payload: PayloadExtractorChain,
+    policy: TlsPolicy,
+    session: Option<TlsSession>,
 
     // TODO: Make type for this
     #[cfg(feature = "native-tls")]
@@ -34,11 +100,27 @@ pub struct TlsAnnotator{
 }
 
 impl TlsAnnotator {
-    pub fn new(cfg: &config::SdkInfo) -> Result<impl Annotator<Error = Error> + Tls> {
+    pub fn new(cfg: &config::SdkInfo) -> Result<impl PayloadAnnotator + Tls> {
+        let policy = match &cfg.tls {
+            Some(tls) => TlsPolicy::new(&tls.policy)?,
+            None => TlsPolicy::default(),
+        };
+        // native-tls does not expose the negotiated version or cipher suite, so with it as the only
+        // backend a policy on either could never be satisfied
+        #[cfg(all(feature = "native-tls", not(feature = "rustls")))]
+        if policy.requires_negotiated_parameters() {
+            return Err(crate::errors::Error::IncorrectConfig)
+        }
+
         Ok(TlsAnnotator {
             hash: cfg.hash.hash_type.clone(),
+            hasher: new_hash_provider(&cfg.hash.hash_type)?,
             kind: constants::ANNOTATION_TLS.clone(),
             sign: new_signature_provider(&cfg.signature)?,
+            host: new_host_identity(&cfg.host)?,
+            payload: new_payload_extractor(&cfg.payload),
+            policy,
+            session: None,
             #[cfg(feature = "native-tls")]
             conn_native: None,
             #[cfg(feature = "rustls")]
@@ -50,11 +132,20 @@ impl TlsAnnotator {
 }
 
 pub trait Tls {
+    /// Attach a snapshot of an already negotiated session. Takes precedence over any connection
+    /// set through `set_connection_native`/`set_connection_rustls`. The annotation is satisfied
+    /// only if the session passes the configured [`TlsPolicy`]
+    fn set_session(&mut self, session: TlsSession);
+    #[cfg(feature = "rustls")]
+    fn set_session_rustls(&mut self, state: &rustls::CommonState);
+    #[cfg(feature = "rustls")]
+    fn set_tokio_stream<IO>(&mut self, stream: &tokio_rustls::TlsStream<IO>);
     #[cfg(feature = "native-tls")]
     fn set_connection_native(&mut self, tls_stream: TlsStream<TcpStream>);
     #[cfg(feature = "rustls")]
     fn set_connection_rustls(&mut self, conn: Connection, stream: TcpStream);
 
+    fn check_tls_session(&self) -> bool;
     #[cfg(feature = "native-tls")]
     fn check_tls_stream_native(&self) -> bool;
     #[cfg(feature = "rustls")]
@@ -62,6 +153,24 @@ pub trait Tls {
 }
 
 impl Tls for TlsAnnotator {
+    fn set_session(&mut self, session: TlsSession) {
+        self.session = Some(session);
+    }
+
+    #[cfg(feature = "rustls")]
+    fn set_session_rustls(&mut self, state: &rustls::CommonState) {
+        self.set_session(TlsSession::from(state))
+    }
+
+    #[cfg(feature = "rustls")]
+    fn set_tokio_stream<IO>(&mut self, stream: &tokio_rustls::TlsStream<IO>) {
+        let session = match stream {
+            tokio_rustls::TlsStream::Client(stream) => TlsSession::from(stream.get_ref().1),
+            tokio_rustls::TlsStream::Server(stream) => TlsSession::from(stream.get_ref().1),
+        };
+        self.set_session(session)
+    }
+
     #[cfg(feature = "native-tls")]
     fn set_connection_native(&mut self, tls_stream: TlsStream<TcpStream>) {
         self.conn_native = Some(Mutex::new(tls_stream));
@@ -73,10 +182,32 @@ impl Tls for TlsAnnotator {
         self.conn_rustls = Some(conn);
     }
 
+    fn check_tls_session(&self) -> bool {
+        match &self.session {
+            Some(session) => self.policy.evaluate(session),
+            None => false
+        }
+    }
+
     #[cfg(feature = "native-tls")]
     fn check_tls_stream_native(&self) -> bool {
         match &self.conn_native {
-            Some(conn) => conn.lock().unwrap().peer_certificate().is_ok(),
+            Some(conn) => match conn.lock().unwrap().peer_certificate() {
+                Ok(cert) => {
+                    if self.policy.requires_negotiated_parameters() {
+                        warn!("TLS policy checks the version or cipher suite, which native-tls does not expose");
+                        return false
+                    }
+                    // native-tls only exposes the end entity certificate of the peer
+                    let session = TlsSession {
+                        handshake_complete: true,
+                        peer_certificates: cert.and_then(|cert| cert.to_der().ok()).into_iter().collect(),
+                        ..Default::default()
+                    };
+                    self.policy.evaluate(&session)
+                },
+                Err(_) => false
+            },
             None => false
         }
     }
@@ -92,14 +223,25 @@ impl Tls for TlsAnnotator {
 
                 loop {
                     if conn.wants_write() {
-                        conn.write_tls(stream).unwrap();
+                        if let Err(e) = conn.write_tls(stream) {
+                            info!("Failed to write to TLS stream: {:?}", e);
+                            return false
+                        }
                     }
 
                     if conn.wants_read() {
-                        if stream.read(&mut buf).unwrap() == 0 {
-                            break;
+                        match stream.read(&mut buf) {
+                            Ok(0) => break,
+                            Ok(_) => {},
+                            Err(e) => {
+                                info!("Failed to read from TLS stream: {:?}", e);
+                                return false
+                            }
+                        }
+                        if let Err(e) = conn.read_tls(stream) {
+                            info!("Failed to read TLS records: {:?}", e);
+                            return false
                         }
-                        conn.read_tls(stream).unwrap();
                     }
 
                     if !conn.is_handshaking() {
@@ -113,7 +255,7 @@ impl Tls for TlsAnnotator {
                 }
 
                 let mut buffer = [0; 1];
-                match stream.peek(&mut buffer) {
+                let alive = match stream.peek(&mut buffer) {
                     Ok(_) => true,
                     Err(e) => {
                         match e.kind() {
@@ -128,7 +270,8 @@ impl Tls for TlsAnnotator {
                             }
                         }
                     }
-                }
+                };
+                alive && self.policy.evaluate(&TlsSession::from(&**conn))
             } else {
                 false
             }
@@ -144,26 +287,36 @@ impl Tls for TlsAnnotator {
 impl Annotator for TlsAnnotator {
     type Error = crate::errors::Error;
     fn annotate(&mut self, data: &[u8]) -> Result<Annotation> {
-        let hasher = new_hash_provider(&self.hash)?;
-        let signable: std::result::Result<Signable, serde_json::Error> = serde_json::from_slice(data);
-        let key = match signable {
-            Ok(signable) => derive_hash(hasher, signable.seed.as_bytes()),
-            Err(_) => derive_hash(hasher, data),
+        let payload = self.payload.extract(data);
+        self.annotate_payload(data, &payload)
+    }
+}
+
+impl PayloadAnnotator for TlsAnnotator {
+    fn kind(&self) -> &constants::AnnotationType {
+        &self.kind
+    }
+
+    fn annotate_payload(&mut self, data: &[u8], payload: &Payload) -> Result<Annotation> {
+        let mut annotation = self.annotate_unsigned(data, payload)?;
+        let signature = serialise_and_sign(&self.sign, &annotation)?;
+        annotation.with_signature(&signature);
+        Ok(annotation)
+    }
+
+    fn annotate_unsigned(&mut self, _data: &[u8], payload: &Payload) -> Result<Annotation> {
+        let key = self.hasher.derive(&payload.content);
+        let is_satisfied = if self.session.is_some() {
+            self.check_tls_session()
+        } else {
+            #[cfg(all(not(feature = "rustls"), feature = "native-tls"))]
+            let is_satisfied = self.check_tls_stream_native();
+            #[cfg(feature = "rustls")]
+            let is_satisfied = self.check_tls_stream_rustls();
+            is_satisfied
         };
-        match gethostname::gethostname().to_str() {
-            Some(host) => {
-                #[cfg(all(not(feature = "rustls"), feature = "native-tls"))]
-                let is_satisfied = self.check_tls_stream_native();
-                #[cfg(feature = "rustls")]
-                let is_satisfied = self.check_tls_stream_rustls();
-
-                let mut annotation = Annotation::new(&key, self.hash.clone(), host, self.kind.clone(), is_satisfied);
-                let signature = serialise_and_sign(&self.sign, &annotation)?;
-                annotation.with_signature(&signature);
-                Ok(annotation)
-            },
-            None => Err(Error::NoHostName)
-        }
+
+        Ok(Annotation::new(&key, self.hash.clone(), &self.host, self.kind.clone(), is_satisfied))
     }
 }
 
@@ -174,9 +327,8 @@ mod tls_tests {
     use rustls::ClientConnection;
     use std::net::TcpStream;
     use crate::{config, providers::sign_provider::get_priv_key};
-    use crate::annotations::{Annotator, constants, TlsAnnotator};
+    use crate::annotations::{Annotator, constants, TlsAnnotator, TlsSession, TlsSide};
     use crate::config::Signable;
-    #[cfg(feature = "rustls")]
     use super::Tls;
 
     #[test]
@@ -193,13 +345,13 @@ mod tls_tests {
         let serialised = serde_json::to_vec(&signable).unwrap();
 
         let mut tls_annotator_1 = TlsAnnotator::new(&config).unwrap();
-        let mut tls_annotator_2 = TlsAnnotator::new(&config2).unwrap();
+        // The hash provider is resolved when the annotator is built
+        let invalid_annotator = TlsAnnotator::new(&config2);
 
         let valid_annotation = tls_annotator_1.annotate(&serialised).unwrap();
-        let invalid_annotation = tls_annotator_2.annotate(&serialised);
 
         assert!(valid_annotation.validate_base());
-        assert!(invalid_annotation.is_err());
+        assert!(invalid_annotator.is_err());
     }
 
     #[cfg(feature = "rustls")]
@@ -251,6 +403,58 @@ mod tls_tests {
         assert!(!annotation.is_satisfied)
     }
 
+    #[test]
+    fn make_tls_session_annotation() {
+        let config: config::SdkInfo = serde_json::from_slice(crate::CONFIG_BYTES.as_slice()).unwrap();
+
+        let data = String::from("Some random data");
+        let sig = hex::encode([0u8; crypto::signatures::ed25519::SIGNATURE_LENGTH]);
+
+        let signable = Signable::new(data, sig);
+        let serialised = serde_json::to_vec(&signable).unwrap();
+
+        let mut tls_annotator = TlsAnnotator::new(&config).unwrap();
+        tls_annotator.set_session(TlsSession {
+            handshake_complete: true,
+            protocol_version: Some(0x0304),
+            cipher_suite: Some("TLS13_AES_256_GCM_SHA384".to_string()),
+            peer_certificates: vec![],
+            side: None,
+        });
+
+        let annotation = tls_annotator.annotate(&serialised).unwrap();
+
+        assert!(annotation.validate_base());
+        assert_eq!(annotation.kind, *constants::ANNOTATION_TLS);
+        assert!(annotation.is_satisfied)
+    }
+
+    #[cfg(feature = "rustls")]
+    #[test]
+    fn unsatisfied_handshaking_session_annotation() {
+        let config: config::SdkInfo = serde_json::from_slice(crate::CONFIG_BYTES.as_slice()).unwrap();
+
+        let data = String::from("Some random data");
+        let sig = hex::encode([0u8; crypto::signatures::ed25519::SIGNATURE_LENGTH]);
+
+        let signable = Signable::new(data, sig);
+        let serialised = serde_json::to_vec(&signable).unwrap();
+
+        // A connection that has not yet been driven has not completed its handshake
+        let conn = make_client_connection().unwrap();
+        let session = TlsSession::from(&conn);
+        assert!(!session.handshake_complete);
+        assert!(session.protocol_version.is_none());
+        assert_eq!(session.side, Some(TlsSide::Client));
+
+        let mut tls_annotator = TlsAnnotator::new(&config).unwrap();
+        tls_annotator.set_session(session);
+        let annotation = tls_annotator.annotate(&serialised).unwrap();
+
+        assert!(annotation.validate_base());
+        assert!(!annotation.is_satisfied)
+    }
+
     #[cfg(feature = "rustls")]
     fn make_client_connection() -> Result<ClientConnection, Box<dyn std::error::Error>> {
         let mut root_store = rustls::RootCertStore::empty();
@@ -0,0 +1,474 @@
+use std::io::BufReader;
+use crypto::hashes::sha::{SHA256, SHA256_LEN};
+use log::debug;
+use x509_parser::extensions::GeneralName;
+use crate::annotations::TlsSession;
+use crate::config::TlsPolicyInfo;
+use crate::errors::{Error, Result};
+
+static SUPPORTED_SIG_ALGS: &[&webpki::SignatureAlgorithm] = &[
+    &webpki::ECDSA_P256_SHA256,
+    &webpki::ECDSA_P256_SHA384,
+    &webpki::ECDSA_P384_SHA256,
+    &webpki::ECDSA_P384_SHA384,
+    &webpki::ED25519,
+    &webpki::RSA_PKCS1_2048_8192_SHA256,
+    &webpki::RSA_PKCS1_2048_8192_SHA384,
+    &webpki::RSA_PKCS1_2048_8192_SHA512,
+    &webpki::RSA_PKCS1_3072_8192_SHA384,
+];
+
+/// A subject alternative name, kept apart by type so each is matched by its own rules
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum SubjectName {
+    Dns(String),
+    Uri(String),
+    Email(String),
+    Ip(String),
+}
+
+impl SubjectName {
+    fn matches(&self, pattern: &str) -> bool {
+        match self {
+            SubjectName::Dns(name) => wildcard_match(pattern, name),
+            SubjectName::Uri(uri) => uri_match(pattern, uri),
+            SubjectName::Email(address) => pattern.eq_ignore_ascii_case(address),
+            SubjectName::Ip(address) => pattern == address,
+        }
+    }
+}
+
+/// Which end of the connection the annotating host is on. Decides the key usage the peer
+/// certificate is validated for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TlsSide {
+    Client,
+    Server,
+}
+
+/// A [`TlsPolicyInfo`] resolved into the values needed to evaluate sessions against it
+#[derive(Debug, Default, Clone)]
+pub struct TlsPolicy {
+    min_version: Option<u16>,
+    cipher_suites: Vec<String>,
+    require_mutual: bool,
+    trust_anchors: Vec<Vec<u8>>,
+    san_patterns: Vec<String>,
+    pins: Vec<String>,
+}
+
+impl TlsPolicy {
+    pub fn new(cfg: &TlsPolicyInfo) -> Result<Self> {
+        let min_version = match &cfg.min_version {
+            Some(version) => Some(parse_version(version)?),
+            None => None,
+        };
+
+        let trust_anchors = match &cfg.ca_bundle {
+            Some(path) => load_ca_bundle(path)?,
+            None => Vec::new(),
+        };
+
+        Ok(TlsPolicy {
+            min_version,
+            cipher_suites: cfg.cipher_suites.clone(),
+            require_mutual: cfg.require_mutual,
+            trust_anchors,
+            san_patterns: cfg.san_patterns.clone(),
+            pins: cfg.pins.iter()
+                .map(|pin| pin.trim_start_matches("sha256/").to_string())
+                .collect(),
+        })
+    }
+
+    /// Whether the policy checks the negotiated protocol version or cipher suite
+    pub fn requires_negotiated_parameters(&self) -> bool {
+        self.min_version.is_some() || !self.cipher_suites.is_empty()
+    }
+
+    /// Returns true if the session meets every requirement of the policy
+    pub fn evaluate(&self, session: &TlsSession) -> bool {
+        if !session.handshake_complete {
+            debug!("TLS handshake has not completed");
+            return false
+        }
+
+        if let Some(min_version) = self.min_version {
+            match session.protocol_version {
+                Some(version) if version >= min_version => {},
+                _ => {
+                    debug!("TLS protocol version {:?} is below minimum {:#06x}", session.protocol_version, min_version);
+                    return false
+                }
+            }
+        }
+
+        if !self.cipher_suites.is_empty() {
+            match &session.cipher_suite {
+                Some(suite) if self.cipher_suites.contains(suite) => {},
+                _ => {
+                    debug!("TLS cipher suite {:?} is not allowed", session.cipher_suite);
+                    return false
+                }
+            }
+        }
+
+        if self.require_mutual && session.peer_certificates.is_empty() {
+            debug!("Peer did not present a certificate");
+            return false
+        }
+
+        if !self.trust_anchors.is_empty() && !self.validate_chain(session) {
+            debug!("Peer certificate chain did not validate against the CA bundle");
+            return false
+        }
+
+        if !self.san_patterns.is_empty() && !self.match_san(session) {
+            debug!("Peer certificate subject alternative names did not match");
+            return false
+        }
+
+        if !self.pins.is_empty() && !self.match_pins(session) {
+            debug!("Peer certificate chain did not match any pin");
+            return false
+        }
+
+        true
+    }
+
+    fn validate_chain(&self, session: &TlsSession) -> bool {
+        let (end_entity, intermediates) = match session.peer_certificates.split_first() {
+            Some(chain) => chain,
+            None => return false
+        };
+
+        let anchors: Vec<webpki::TrustAnchor> = self.trust_anchors.iter()
+            .filter_map(|der| webpki::TrustAnchor::try_from_cert_der(der).ok())
+            .collect();
+        let intermediates: Vec<&[u8]> = intermediates.iter().map(|der| der.as_slice()).collect();
+        let cert = match webpki::EndEntityCert::try_from(end_entity.as_slice()) {
+            Ok(cert) => cert,
+            Err(_) => return false
+        };
+        let now = match webpki::Time::try_from(std::time::SystemTime::now()) {
+            Ok(now) => now,
+            Err(_) => return false
+        };
+
+        // The peer of a server is a client and vice versa
+        let usages = match session.side {
+            Some(TlsSide::Server) => vec![webpki::KeyUsage::client_auth()],
+            Some(TlsSide::Client) => vec![webpki::KeyUsage::server_auth()],
+            None => vec![webpki::KeyUsage::server_auth(), webpki::KeyUsage::client_auth()],
+        };
+
+        usages.into_iter().any(|usage| {
+            cert.verify_for_usage(SUPPORTED_SIG_ALGS, &anchors, &intermediates, now, usage, &[]).is_ok()
+        })
+    }
+
+    fn match_san(&self, session: &TlsSession) -> bool {
+        let end_entity = match session.peer_certificates.first() {
+            Some(cert) => cert,
+            None => return false
+        };
+
+        let names = match subject_alt_names(end_entity) {
+            Ok(names) => names,
+            Err(_) => return false
+        };
+        names.iter().any(|name| self.san_patterns.iter().any(|pattern| name.matches(pattern)))
+    }
+
+    fn match_pins(&self, session: &TlsSession) -> bool {
+        session.peer_certificates.iter().any(|cert| {
+            match spki_digest(cert) {
+                Ok(digest) => self.pins.contains(&digest),
+                Err(_) => false
+            }
+        })
+    }
+}
+
+fn parse_version(version: &str) -> Result<u16> {
+    match version.trim_start_matches("TLS").trim_start_matches('v') {
+        "1.0" => Ok(0x0301),
+        "1.1" => Ok(0x0302),
+        "1.2" => Ok(0x0303),
+        "1.3" => Ok(0x0304),
+        _ => Err(Error::IncorrectConfig)
+    }
+}
+
+fn load_ca_bundle(path: &str) -> Result<Vec<Vec<u8>>> {
+    let file = std::fs::File::open(path).map_err(Error::CaBundleError)?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file)).map_err(Error::CaBundleError)?;
+    // An empty bundle would leave nothing to validate against and skip the chain check
+    if certs.is_empty() {
+        return Err(Error::InvalidCertificate(format!("no certificates in CA bundle {}", path)))
+    }
+    for cert in &certs {
+        webpki::TrustAnchor::try_from_cert_der(cert)
+            .map_err(|e| Error::InvalidCertificate(format!("{:?}", e)))?;
+    }
+    Ok(certs)
+}
+
+fn subject_alt_names(der: &[u8]) -> Result<Vec<SubjectName>> {
+    let (_, cert) = x509_parser::parse_x509_certificate(der)
+        .map_err(|e| Error::InvalidCertificate(e.to_string()))?;
+    let san = cert.subject_alternative_name()
+        .map_err(|e| Error::InvalidCertificate(e.to_string()))?;
+
+    let mut names = Vec::new();
+    if let Some(san) = san {
+        for name in &san.value.general_names {
+            match name {
+                GeneralName::DNSName(name) => names.push(SubjectName::Dns(name.to_string())),
+                GeneralName::URI(uri) => names.push(SubjectName::Uri(uri.to_string())),
+                GeneralName::RFC822Name(address) => names.push(SubjectName::Email(address.to_string())),
+                GeneralName::IPAddress(bytes) => {
+                    if let Ok(octets) = <[u8; 4]>::try_from(*bytes) {
+                        names.push(SubjectName::Ip(std::net::IpAddr::from(octets).to_string()))
+                    } else if let Ok(octets) = <[u8; 16]>::try_from(*bytes) {
+                        names.push(SubjectName::Ip(std::net::IpAddr::from(octets).to_string()))
+                    }
+                },
+                _ => {}
+            }
+        }
+    }
+    Ok(names)
+}
+
+fn spki_digest(der: &[u8]) -> Result<String> {
+    let (_, cert) = x509_parser::parse_x509_certificate(der)
+        .map_err(|e| Error::InvalidCertificate(e.to_string()))?;
+    let mut digest = [0_u8; SHA256_LEN];
+    SHA256(cert.public_key().raw, &mut digest);
+    Ok(base64::encode(digest))
+}
+
+/// Case insensitive match where a `*` in the left-most label of the pattern stands in for part
+/// or all of the left-most label of the value, and never spans a `.` (RFC 6125 section 6.4.3)
+fn wildcard_match(pattern: &str, value: &str) -> bool {
+    let pattern = pattern.to_lowercase();
+    let value = value.to_lowercase();
+    let (pattern_label, pattern_rest) = pattern.split_once('.').unwrap_or((&pattern, ""));
+    let (value_label, value_rest) = value.split_once('.').unwrap_or((&value, ""));
+    if pattern_rest.contains('*') || pattern_rest != value_rest {
+        return false
+    }
+
+    match pattern_label.split_once('*') {
+        None => pattern_label == value_label,
+        Some((prefix, suffix)) => {
+            !suffix.contains('*')
+                && !value_label.is_empty()
+                && value_label.len() >= prefix.len() + suffix.len()
+                && value_label.starts_with(prefix)
+                && value_label.ends_with(suffix)
+        }
+    }
+}
+
+/// Exact match, or a prefix ending in `/*` followed by exactly one path segment, or in `/**`
+/// followed by one or more
+fn uri_match(pattern: &str, uri: &str) -> bool {
+    let (prefix, any_depth) = match (pattern.strip_suffix("/**"), pattern.strip_suffix("/*")) {
+        (Some(prefix), _) => (prefix, true),
+        (None, Some(prefix)) => (prefix, false),
+        (None, None) => return pattern == uri,
+    };
+    let rest = match uri.strip_prefix(prefix).and_then(|rest| rest.strip_prefix('/')) {
+        Some(rest) => rest,
+        None => return false,
+    };
+    !rest.contains(['?', '#'])
+        && rest.split('/').all(|segment| !segment.is_empty())
+        && (any_depth || !rest.contains('/'))
+}
+
+
+#[cfg(test)]
+mod tls_policy_tests {
+    use crate::annotations::{TlsSession, TlsSide};
+    use crate::config::TlsPolicyInfo;
+    use crate::errors::Error;
+    use super::{SubjectName, TlsPolicy, uri_match, wildcard_match};
+
+    const DEVICE_PIN: &str = "T3TL9KwX6qchqicPOTACYXUt1AxZUNx2ILtQCLl1B4k=";
+
+    fn read_cert(path: &str) -> Vec<u8> {
+        let pem = std::fs::read(path).unwrap();
+        rustls_pemfile::certs(&mut pem.as_slice()).unwrap().remove(0)
+    }
+
+    // A client presenting the test device certificate, issued by resources/test_certs/ca.pem
+    fn device_session() -> TlsSession {
+        TlsSession {
+            peer_certificates: vec![read_cert("resources/test_certs/device.pem")],
+            side: Some(TlsSide::Server),
+            ..session()
+        }
+    }
+
+    fn session() -> TlsSession {
+        TlsSession {
+            handshake_complete: true,
+            protocol_version: Some(0x0303),
+            cipher_suite: Some("TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384".to_string()),
+            peer_certificates: vec![],
+            side: None,
+        }
+    }
+
+    #[test]
+    fn default_policy_requires_handshake() {
+        let policy = TlsPolicy::new(&TlsPolicyInfo::default()).unwrap();
+        assert!(policy.evaluate(&session()));
+
+        let mut handshaking = session();
+        handshaking.handshake_complete = false;
+        assert!(!policy.evaluate(&handshaking))
+    }
+
+    #[test]
+    fn version_suite_and_mutual_requirements() {
+        let cfg = TlsPolicyInfo {
+            min_version: Some("1.3".to_string()),
+            ..Default::default()
+        };
+        assert!(!TlsPolicy::new(&cfg).unwrap().evaluate(&session()));
+
+        let cfg = TlsPolicyInfo {
+            cipher_suites: vec!["TLS13_AES_256_GCM_SHA384".to_string()],
+            ..Default::default()
+        };
+        assert!(!TlsPolicy::new(&cfg).unwrap().evaluate(&session()));
+
+        let cfg = TlsPolicyInfo {
+            require_mutual: true,
+            ..Default::default()
+        };
+        assert!(!TlsPolicy::new(&cfg).unwrap().evaluate(&session()));
+
+        let cfg = TlsPolicyInfo {
+            min_version: Some("not a version".to_string()),
+            ..Default::default()
+        };
+        assert!(TlsPolicy::new(&cfg).is_err())
+    }
+
+    #[test]
+    fn negotiated_parameter_requirements() {
+        assert!(!TlsPolicy::new(&TlsPolicyInfo::default()).unwrap().requires_negotiated_parameters());
+        let cfg = TlsPolicyInfo {
+            min_version: Some("1.2".to_string()),
+            ..Default::default()
+        };
+        assert!(TlsPolicy::new(&cfg).unwrap().requires_negotiated_parameters());
+    }
+
+    #[test]
+    fn wildcard_patterns() {
+        assert!(wildcard_match("*.example.com", "device.example.com"));
+        assert!(wildcard_match("exact.example.com", "EXACT.example.com"));
+        assert!(!wildcard_match("*.example.com", "example.org"));
+        assert!(!wildcard_match("exact.example.com", "exact.example.com.evil"));
+        assert!(wildcard_match("dev*.example.com", "device.example.com"));
+    }
+
+    #[test]
+    fn wildcard_stays_in_left_most_label() {
+        assert!(!wildcard_match("*.example.com", "a.device.example.com"));
+        assert!(!wildcard_match("*.example.com", "example.com"));
+        assert!(!wildcard_match("*.example.com", ".example.com"));
+        assert!(!wildcard_match("device.*.com", "device.example.com"));
+        assert!(!wildcard_match("*evil.com", "device.example.evil.com"));
+        assert!(!wildcard_match("**.example.com", "device.example.com"));
+    }
+
+    #[test]
+    fn uri_patterns_match_path_segments() {
+        // A dot before the `*` is part of the authority, not a DNS label
+        assert!(uri_match("spiffe://example.org/*", "spiffe://example.org/collector"));
+        assert!(!uri_match("spiffe://example.org/*", "spiffe://example.org/ns/collector"));
+        // A `*` covers one segment, never the rest of the path
+        assert!(!uri_match("spiffe://cluster/*", "spiffe://cluster/ns/collector"));
+        assert!(uri_match("spiffe://cluster/**", "spiffe://cluster/ns/collector"));
+        assert!(!uri_match("spiffe://cluster/**", "spiffe://clusterevil/ns"));
+        assert!(!uri_match("spiffe://cluster/*", "spiffe://cluster/"));
+        assert!(uri_match("spiffe://cluster/ns/collector", "spiffe://cluster/ns/collector"));
+        assert!(!uri_match("spiffe://cluster/ns/collector", "spiffe://cluster/ns/collector/extra"));
+    }
+
+    #[test]
+    fn wildcards_only_for_dns_names() {
+        let uri = SubjectName::Uri("spiffe://device.example.com".to_string());
+        assert!(!uri.matches("*.example.com"));
+        let email = SubjectName::Email("device@example.com".to_string());
+        assert!(!email.matches("*@example.com"));
+        assert!(email.matches("Device@example.com"));
+        assert!(SubjectName::Dns("device.example.com".to_string()).matches("*.example.com"));
+    }
+
+    #[test]
+    fn chain_validates_against_ca_bundle() {
+        let cfg = TlsPolicyInfo {
+            ca_bundle: Some("resources/test_certs/ca.pem".to_string()),
+            ..Default::default()
+        };
+        let policy = TlsPolicy::new(&cfg).unwrap();
+        assert!(policy.evaluate(&device_session()));
+        // A peer that presented no certificates has no chain to validate
+        assert!(!policy.evaluate(&session()));
+
+        let cfg = TlsPolicyInfo {
+            ca_bundle: Some("resources/test_certs/other_ca.pem".to_string()),
+            ..Default::default()
+        };
+        assert!(!TlsPolicy::new(&cfg).unwrap().evaluate(&device_session()));
+    }
+
+    #[test]
+    fn empty_ca_bundle_rejected() {
+        let cfg = TlsPolicyInfo {
+            ca_bundle: Some("resources/test_certs/invalid_bundle.pem".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(TlsPolicy::new(&cfg), Err(Error::InvalidCertificate(_))));
+    }
+
+    #[test]
+    fn san_patterns_match_certificate() {
+        for pattern in ["*.example.com", "spiffe://cluster/ns/*", "spiffe://cluster/**"] {
+            let cfg = TlsPolicyInfo {
+                san_patterns: vec![pattern.to_string()],
+                ..Default::default()
+            };
+            assert!(TlsPolicy::new(&cfg).unwrap().evaluate(&device_session()), "{}", pattern);
+        }
+
+        let cfg = TlsPolicyInfo {
+            san_patterns: vec!["*.example.org".to_string(), "*.com".to_string(), "spiffe://cluster/*".to_string()],
+            ..Default::default()
+        };
+        assert!(!TlsPolicy::new(&cfg).unwrap().evaluate(&device_session()));
+    }
+
+    #[test]
+    fn spki_pins() {
+        let cfg = TlsPolicyInfo {
+            pins: vec![format!("sha256/{}", DEVICE_PIN)],
+            ..Default::default()
+        };
+        assert!(TlsPolicy::new(&cfg).unwrap().evaluate(&device_session()));
+
+        let cfg = TlsPolicyInfo {
+            pins: vec![base64::encode([0u8; 32])],
+            ..Default::default()
+        };
+        assert!(!TlsPolicy::new(&cfg).unwrap().evaluate(&device_session()));
+    }
+}
@@ -1,15 +1,17 @@
-use crate::annotations::{Annotation, Annotator, constants};
+use std::sync::Arc;
+use crate::annotations::{Annotation, Annotator, PayloadAnnotator, constants};
 use crate::config;
-use crate::errors::{Result, Error};
-use alvarium_annotator::{derive_hash, serialise_and_sign};
+use crate::errors::Result;
+use alvarium_annotator::{serialise_and_sign, HashProvider};
 
 
 #[cfg(unix)]
 use std::os::linux::fs::MetadataExt;
 #[cfg(windows)]
 use std::os::windows::fs::MetadataExt;
-use crate::config::Signable;
-use crate::factories::{new_hash_provider, new_signature_provider};
+use crate::factories::{new_hash_provider, new_host_identity, new_payload_extractor, new_signature_provider};
+use crate::providers::payload_provider::{Payload, PayloadExtractorChain};
+use crate::providers::hash_provider::HashProviderWrapper;
 use crate::providers::sign_provider::SignatureProviderWrap;
 
 const UNIX_TPM_PATH: &str = "/dev/tpm0"; // Adjust the path as needed
@@ -17,15 +19,21 @@ const UNIX_TPM_PATH: &str = "/dev/tpm0"; // Adjust the path as needed
 pub struct TpmAnnotator {
     hash: constants::HashType,
     kind: constants::AnnotationType,
+    hasher: HashProviderWrapper,
     sign: SignatureProviderWrap,
+    host: Arc<str>,
+    payload: PayloadExtractorChain,
 }
 
 impl TpmAnnotator {
-    pub fn new(cfg: &config::SdkInfo) -> Result<impl Annotator<Error = Error>> {
+    pub fn new(cfg: &config::SdkInfo) -> Result<impl PayloadAnnotator> {
         Ok(TpmAnnotator {
             hash: cfg.hash.hash_type.clone(),
+            hasher: new_hash_provider(&cfg.hash.hash_type)?,
             kind: constants::ANNOTATION_TPM.clone(),
             sign: new_signature_provider(&cfg.signature)?,
+            host: new_host_identity(&cfg.host)?,
+            payload: new_payload_extractor(&cfg.payload),
         })
     }
 
@@ -61,26 +69,31 @@ impl TpmAnnotator {
 impl Annotator for TpmAnnotator {
     type Error = crate::errors::Error;
     fn annotate(&mut self, data: &[u8]) -> Result<Annotation> {
-        let hasher = new_hash_provider(&self.hash)?;
-        let signable: std::result::Result<Signable, serde_json::Error> = serde_json::from_slice(data);
-        let key = match signable {
-            Ok(signable) => derive_hash(hasher, signable.seed.as_bytes()),
-            Err(_) => derive_hash(hasher, data),
-        };
-        match gethostname::gethostname().to_str() {
-            Some(host) => {
-                #[cfg(unix)]
-                let is_satisfied = self.check_tpm_presence_unix();
-                #[cfg(windows)]
-                let is_satisfied = self.check_tpm_presence_windows();
-
-                let mut annotation = Annotation::new(&key, self.hash.clone(), host, self.kind.clone(), is_satisfied);
-                let signature = serialise_and_sign(&self.sign, &annotation)?;
-                annotation.with_signature(&signature);
-                Ok(annotation)
-            },
-            None => Err(Error::NoHostName)
-        }
+        let payload = self.payload.extract(data);
+        self.annotate_payload(data, &payload)
+    }
+}
+
+impl PayloadAnnotator for TpmAnnotator {
+    fn kind(&self) -> &constants::AnnotationType {
+        &self.kind
+    }
+
+    fn annotate_payload(&mut self, data: &[u8], payload: &Payload) -> Result<Annotation> {
+        let mut annotation = self.annotate_unsigned(data, payload)?;
+        let signature = serialise_and_sign(&self.sign, &annotation)?;
+        annotation.with_signature(&signature);
+        Ok(annotation)
+    }
+
+    fn annotate_unsigned(&mut self, _data: &[u8], payload: &Payload) -> Result<Annotation> {
+        let key = self.hasher.derive(&payload.content);
+        #[cfg(unix)]
+        let is_satisfied = self.check_tpm_presence_unix();
+        #[cfg(windows)]
+        let is_satisfied = self.check_tpm_presence_windows();
+
+        Ok(Annotation::new(&key, self.hash.clone(), &self.host, self.kind.clone(), is_satisfied))
     }
 }
 
@@ -107,13 +120,13 @@ mod tpm_tests {
         let serialised = serde_json::to_vec(&signable).unwrap();
 
         let mut tpm_annotator_1 = TpmAnnotator::new(&config).unwrap();
-        let mut tpm_annotator_2 = TpmAnnotator::new(&config2).unwrap();
+        // The hash provider is resolved when the annotator is built
+        let invalid_annotator = TpmAnnotator::new(&config2);
 
         let valid_annotation = tpm_annotator_1.annotate(&serialised).unwrap();
-        let invalid_annotation = tpm_annotator_2.annotate(&serialised);
 
         assert!(valid_annotation.validate_base());
-        assert!(invalid_annotation.is_err());
+        assert!(invalid_annotator.is_err());
     }
 
 
@@ -0,0 +1,212 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use async_trait::async_trait;
+use rayon::ThreadPool;
+use crate::annotations::{Annotation, Annotator, PayloadAnnotator};
+use crate::annotations::constants::AnnotationType;
+use crate::providers::payload_provider::Payload;
+use crate::errors::{Error, Result};
+use crate::{SdkAnnotator, SdkAsyncAnnotator};
+
+/// An annotator that can await I/O, such as a TPM quote, a remote signer or a key fetch,
+/// without blocking the runtime. The SDK runs all of its annotators for a call concurrently
+#[async_trait]
+pub trait AsyncAnnotator: Send {
+    /// The annotation type this annotator produces, which the SDK selects it by
+    fn kind(&self) -> &AnnotationType;
+
+    /// Details of the environment the annotations were made in, see [`PayloadAnnotator::context`]
+    fn context(&self) -> BTreeMap<String, String> {
+        BTreeMap::new()
+    }
+
+    async fn annotate(&mut self, data: &[u8]) -> Result<Annotation>;
+
+    /// Annotates a payload the SDK has already extracted from `data`, see [`PayloadAnnotator`].
+    /// Both are shared by all of the annotators of an SDK call, so an annotator that outlives the
+    /// call with them (e.g. on a thread pool) holds on to them rather than copying
+    async fn annotate_payload(&mut self, data: &Arc<[u8]>, _payload: &Arc<Payload>) -> Result<Annotation> {
+        self.annotate(data).await
+    }
+
+    /// Annotates a payload leaving the signing to the caller, see
+    /// [`PayloadAnnotator::annotate_unsigned`]
+    async fn annotate_unsigned(&mut self, data: &Arc<[u8]>, payload: &Arc<Payload>) -> Result<Annotation> {
+        self.annotate_payload(data, payload).await
+    }
+}
+
+/// Runs a synchronous annotator as an [`AsyncAnnotator`]. Without a pool the annotation is
+/// produced inline when polled, which suits annotators that do not block for long. With a pool
+/// it is produced on one of the pool's threads, so the annotators of one SDK call run in parallel
+pub struct SyncAnnotator {
+    kind: AnnotationType,
+    inner: Arc<Mutex<Box<SdkAnnotator>>>,
+    pool: Option<Arc<ThreadPool>>,
+}
+
+impl SyncAnnotator {
+    pub fn new(inner: Box<SdkAnnotator>) -> Self {
+        SyncAnnotator { kind: inner.kind().clone(), inner: Arc::new(Mutex::new(inner)), pool: None }
+    }
+
+    pub fn pooled(inner: Box<SdkAnnotator>, pool: Arc<ThreadPool>) -> Self {
+        SyncAnnotator { kind: inner.kind().clone(), inner: Arc::new(Mutex::new(inner)), pool: Some(pool) }
+    }
+
+    pub fn boxed(inner: Box<SdkAnnotator>) -> Box<SdkAsyncAnnotator> {
+        Box::new(SyncAnnotator::new(inner))
+    }
+}
+
+// A panicking annotator must not take the others down with it, its state is still usable
+fn lock(inner: &Mutex<Box<SdkAnnotator>>) -> MutexGuard<'_, Box<SdkAnnotator>> {
+    inner.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+// Runs on the pool, with the annotator shared into the task since the pool outlives this call
+async fn run_pooled<F>(pool: &ThreadPool, inner: &Arc<Mutex<Box<SdkAnnotator>>>, annotate: F) -> Result<Annotation>
+where
+    F: FnOnce(&mut SdkAnnotator) -> Result<Annotation> + Send + 'static
+{
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    let inner = inner.clone();
+    pool.spawn(move || {
+        // The receiver only goes away if the SDK call was dropped, then nobody needs this
+        let _ = tx.send(annotate(&mut **lock(&inner)));
+    });
+    rx.await.map_err(|_| Error::AnnotatorPoolError("annotator did not complete".to_string()))?
+}
+
+#[async_trait]
+impl AsyncAnnotator for SyncAnnotator {
+    fn kind(&self) -> &AnnotationType {
+        &self.kind
+    }
+
+    fn context(&self) -> BTreeMap<String, String> {
+        lock(&self.inner).context()
+    }
+
+    async fn annotate(&mut self, data: &[u8]) -> Result<Annotation> {
+        match &self.pool {
+            Some(pool) => {
+                let data = data.to_vec();
+                run_pooled(pool, &self.inner, move |annotator| annotator.annotate(&data)).await
+            },
+            None => {
+                let mut annotator = lock(&self.inner);
+                annotator.annotate(data)
+            },
+        }
+    }
+
+    async fn annotate_payload(&mut self, data: &Arc<[u8]>, payload: &Arc<Payload>) -> Result<Annotation> {
+        match &self.pool {
+            Some(pool) => {
+                let (data, payload) = (data.clone(), payload.clone());
+                run_pooled(pool, &self.inner, move |annotator| annotator.annotate_payload(&data, &payload)).await
+            },
+            None => {
+                let mut annotator = lock(&self.inner);
+                annotator.annotate_payload(data, payload)
+            },
+        }
+    }
+
+    async fn annotate_unsigned(&mut self, data: &Arc<[u8]>, payload: &Arc<Payload>) -> Result<Annotation> {
+        match &self.pool {
+            Some(pool) => {
+                let (data, payload) = (data.clone(), payload.clone());
+                run_pooled(pool, &self.inner, move |annotator| annotator.annotate_unsigned(&data, &payload)).await
+            },
+            None => {
+                let mut annotator = lock(&self.inner);
+                annotator.annotate_unsigned(data, payload)
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod async_annotator_tests {
+    use std::sync::Arc;
+    use async_trait::async_trait;
+    use crate::config;
+    use crate::annotations::{mock_annotation, Annotation, AsyncAnnotator, SyncAnnotator, constants};
+    use crate::errors::Result;
+    use crate::factories::new_annotator;
+    use crate::providers::payload_provider::Payload;
+
+    // Gives up its turn before answering, as an annotator waiting on I/O would
+    struct YieldingAnnotator;
+
+    #[async_trait]
+    impl AsyncAnnotator for YieldingAnnotator {
+        fn kind(&self) -> &constants::AnnotationType {
+            &constants::ANNOTATION_SOURCE
+        }
+
+        async fn annotate(&mut self, _data: &[u8]) -> Result<Annotation> {
+            tokio::task::yield_now().await;
+            Ok(mock_annotation())
+        }
+    }
+
+    #[tokio::test]
+    async fn join_sync_and_async_annotators() {
+        let config: config::SdkInfo = serde_json::from_slice(crate::CONFIG_BYTES.as_slice()).unwrap();
+        let pki = new_annotator(constants::ANNOTATION_PKI.clone(), config).unwrap();
+
+        let mut sync_annotator = SyncAnnotator::new(pki);
+        let mut async_annotator = YieldingAnnotator;
+        let data = "Some random data".as_bytes();
+        let annotations = futures::future::join_all([
+            async_annotator.annotate(data),
+            sync_annotator.annotate(data),
+        ]).await;
+
+        assert_eq!(annotations[0].as_ref().unwrap().kind, *constants::ANNOTATION_SOURCE);
+        assert_eq!(annotations[1].as_ref().unwrap().kind, *constants::ANNOTATION_PKI);
+    }
+
+    #[tokio::test]
+    async fn pooled_annotators_keep_order() {
+        let config: config::SdkInfo = serde_json::from_slice(crate::CONFIG_BYTES.as_slice()).unwrap();
+        let pool = std::sync::Arc::new(rayon::ThreadPoolBuilder::new().num_threads(2).build().unwrap());
+
+        let kinds = [
+            constants::ANNOTATION_PKI.clone(),
+            constants::ANNOTATION_SOURCE.clone(),
+            constants::ANNOTATION_TPM.clone(),
+        ];
+        let mut annotators = kinds.iter()
+            .map(|kind| SyncAnnotator::pooled(new_annotator(kind.clone(), config.clone()).unwrap(), pool.clone()))
+            .collect::<Vec<SyncAnnotator>>();
+
+        let data = "Some random data".as_bytes();
+        let annotations = futures::future::join_all(
+            annotators.iter_mut().map(|annotator| annotator.annotate(data))
+        ).await;
+
+        let annotated = annotations.into_iter()
+            .map(|annotation| annotation.unwrap().kind)
+            .collect::<Vec<_>>();
+        assert_eq!(annotated, kinds);
+    }
+
+    #[tokio::test]
+    async fn unsigned_annotations_left_to_caller() {
+        let config: config::SdkInfo = serde_json::from_slice(crate::CONFIG_BYTES.as_slice()).unwrap();
+        let source = new_annotator(constants::ANNOTATION_SOURCE.clone(), config).unwrap();
+        let mut annotator = SyncAnnotator::new(source);
+
+        let data: Arc<[u8]> = Arc::from("Some random data".as_bytes());
+        let payload = Arc::new(Payload::raw(&data));
+        let unsigned = annotator.annotate_unsigned(&data, &payload).await.unwrap();
+        let signed = annotator.annotate_payload(&data, &payload).await.unwrap();
+        assert!(unsigned.signature.is_empty());
+        assert!(!signed.signature.is_empty());
+        assert_eq!(unsigned.key, signed.key);
+    }
+}
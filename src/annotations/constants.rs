@@ -0,0 +1,42 @@
+use serde::{Serialize, Deserialize};
+pub use alvarium_annotator::constants::*;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct LayerType(pub String);
+
+impl LayerType {
+    pub fn is_base_layer_type(&self) -> bool {
+        self == &*LAYER_APP || self == &*LAYER_CICD || self == &*LAYER_HOST
+    }
+}
+
+/// Annotation types provided by this SDK rather than the core alvarium types
+pub fn is_sdk_annotation_type(kind: &AnnotationType) -> bool {
+    [
+        &*ANNOTATION_LINEAGE,
+        &*ANNOTATION_FRESHNESS,
+        &*ANNOTATION_SCHEMA,
+        &*ANNOTATION_EXECUTABLE,
+        &*ANNOTATION_CONTAINER,
+    ].contains(&kind)
+}
+
+// Annotation types, stream types and layers provided by this SDK on top of the core alvarium types
+lazy_static! {
+    pub static ref ANNOTATION_LINEAGE: AnnotationType = AnnotationType("lineage".to_string());
+    pub static ref ANNOTATION_FRESHNESS: AnnotationType = AnnotationType("freshness".to_string());
+    pub static ref ANNOTATION_SCHEMA: AnnotationType = AnnotationType("schema".to_string());
+    pub static ref ANNOTATION_EXECUTABLE: AnnotationType = AnnotationType("executable".to_string());
+    pub static ref ANNOTATION_CONTAINER: AnnotationType = AnnotationType("container".to_string());
+
+    pub static ref STREAM_KAFKA: StreamType = StreamType("kafka".to_string());
+    pub static ref STREAM_NATS: StreamType = StreamType("nats".to_string());
+    pub static ref STREAM_HTTP: StreamType = StreamType("http".to_string());
+    pub static ref STREAM_AMQP: StreamType = StreamType("amqp".to_string());
+    pub static ref STREAM_REDIS: StreamType = StreamType("redis".to_string());
+    pub static ref STREAM_COAP: StreamType = StreamType("coap".to_string());
+
+    pub static ref LAYER_APP: LayerType = LayerType("app".to_string());
+    pub static ref LAYER_CICD: LayerType = LayerType("cicd".to_string());
+    pub static ref LAYER_HOST: LayerType = LayerType("host".to_string());
+}
@@ -1,7 +1,14 @@
 mod annotators;
+mod async_annotator;
+pub mod constants;
+mod payload_annotator;
+mod stamp;
 
 pub use annotators::*;
-pub use alvarium_annotator::{Annotation, Annotator, AnnotationList, constants};
+pub use async_annotator::*;
+pub use payload_annotator::*;
+pub use stamp::*;
+pub use alvarium_annotator::{Annotation, Annotator, AnnotationList};
 
 pub fn mock_annotation() -> Annotation {
     let key = "The hash of the contents";
@@ -0,0 +1,90 @@
+use std::collections::BTreeMap;
+use crate::annotations::{Annotation, Annotator};
+use crate::annotations::constants::AnnotationType;
+use crate::errors::{Error, Result};
+use crate::providers::payload_provider::Payload;
+use crate::SdkAnnotator;
+
+/// An annotator that can work from a payload extracted ahead of time, so the SDK extracts each
+/// message once for all of its annotators rather than once per annotator. The default falls back
+/// to annotating the raw data, so an annotator without its own extraction only needs to name the
+/// annotation type it produces
+pub trait PayloadAnnotator: Annotator<Error = Error> {
+    /// The annotation type this annotator produces, which the SDK selects it by
+    fn kind(&self) -> &AnnotationType;
+
+    /// Details of the environment the annotations were made in, such as the container they ran
+    /// in. The SDK records them alongside each annotation, covered by its signature
+    fn context(&self) -> BTreeMap<String, String> {
+        BTreeMap::new()
+    }
+
+    fn annotate_payload(&mut self, data: &[u8], _payload: &Payload) -> Result<Annotation> {
+        self.annotate(data)
+    }
+
+    /// Same as [`PayloadAnnotator::annotate_payload`], leaving the annotation for the caller to
+    /// sign, as the SDK does once it has stamped it. The default returns the signed annotation,
+    /// whose signature the caller then replaces
+    fn annotate_unsigned(&mut self, data: &[u8], payload: &Payload) -> Result<Annotation> {
+        self.annotate_payload(data, payload)
+    }
+}
+
+/// Adapts an annotator that only implements [`Annotator`] for use where a [`PayloadAnnotator`]
+/// is expected, such as [`SyncAnnotator`](crate::annotations::SyncAnnotator) or the SDK. Such an
+/// annotator does not report its kind, so it is given here
+pub struct PlainAnnotator<A> {
+    kind: AnnotationType,
+    inner: A,
+}
+
+impl<A: Annotator<Error = Error> + Send + 'static> PlainAnnotator<A> {
+    pub fn new(kind: AnnotationType, inner: A) -> Self {
+        PlainAnnotator { kind, inner }
+    }
+
+    pub fn boxed(kind: AnnotationType, inner: A) -> Box<SdkAnnotator> {
+        Box::new(PlainAnnotator::new(kind, inner))
+    }
+}
+
+impl<A: Annotator<Error = Error>> Annotator for PlainAnnotator<A> {
+    type Error = Error;
+    fn annotate(&mut self, data: &[u8]) -> Result<Annotation> {
+        self.inner.annotate(data)
+    }
+}
+
+impl<A: Annotator<Error = Error>> PayloadAnnotator for PlainAnnotator<A> {
+    fn kind(&self) -> &AnnotationType {
+        &self.kind
+    }
+}
+
+
+#[cfg(test)]
+mod payload_annotator_tests {
+    use crate::annotations::{mock_annotation, constants, Annotation, Annotator, AsyncAnnotator, PlainAnnotator, SyncAnnotator};
+    use crate::errors::{Error, Result};
+
+    // Written against the Annotator trait alone
+    struct CustomAnnotator;
+
+    impl Annotator for CustomAnnotator {
+        type Error = Error;
+        fn annotate(&mut self, _data: &[u8]) -> Result<Annotation> {
+            Ok(mock_annotation())
+        }
+    }
+
+    #[tokio::test]
+    async fn adapt_plain_annotator() {
+        let plain = PlainAnnotator::boxed(constants::ANNOTATION_SOURCE.clone(), CustomAnnotator);
+        let mut annotator = SyncAnnotator::boxed(plain);
+        assert_eq!(annotator.kind(), &*constants::ANNOTATION_SOURCE);
+
+        let annotation = annotator.annotate("Some random data".as_bytes()).await.unwrap();
+        assert_eq!(annotation.key, mock_annotation().key);
+    }
+}
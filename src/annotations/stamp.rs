@@ -0,0 +1,108 @@
+use std::collections::BTreeMap;
+use serde::{Serialize, Deserialize};
+use alvarium_annotator::SignProvider;
+use crate::annotations::Annotation;
+use crate::annotations::constants::LayerType;
+use crate::providers::sign_provider::SignatureProviderWrap;
+use crate::errors::Result;
+
+/// The layer an annotation was produced in and an optional tag used to correlate annotations
+/// across layers (e.g. a pipeline run id shared by the cicd and app layers)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Stamp {
+    pub layer: LayerType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+}
+
+impl Stamp {
+    pub fn new(layer: LayerType, tag: Option<String>) -> Self {
+        Stamp { layer, tag }
+    }
+}
+
+/// An [`Annotation`] stamped with its layer and tag, and the context its annotator recorded.
+/// Serialises as the annotation with `layer`, `tag` and `context` fields added, so it still reads
+/// as a plain annotation for consumers unaware of layers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StampedAnnotation {
+    #[serde(flatten)]
+    pub annotation: Annotation,
+    #[serde(flatten)]
+    pub stamp: Stamp,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub context: BTreeMap<String, String>,
+}
+
+impl StampedAnnotation {
+    /// Stamps the annotation and signs it over the stamp, replacing any signature it already had
+    pub fn new(annotation: Annotation, stamp: Stamp, sign: &SignatureProviderWrap) -> Result<Self> {
+        StampedAnnotation::with_context(annotation, stamp, BTreeMap::new(), sign)
+    }
+
+    /// Same as [`StampedAnnotation::new`], recording `context` with the annotation under the
+    /// same signature
+    pub fn with_context(
+        mut annotation: Annotation,
+        stamp: Stamp,
+        context: BTreeMap<String, String>,
+        sign: &SignatureProviderWrap
+    ) -> Result<Self> {
+        annotation.with_signature("");
+        let mut stamped = StampedAnnotation { annotation, stamp, context };
+        let signature = sign.sign(&serde_json::to_vec(&stamped)?)?;
+        stamped.annotation.with_signature(&signature);
+        Ok(stamped)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StampedAnnotationList {
+    pub items: Vec<StampedAnnotation>,
+}
+
+
+#[cfg(test)]
+mod stamp_tests {
+    use alvarium_annotator::SignProvider;
+    use crate::annotations::{mock_annotation, constants, Stamp, StampedAnnotation};
+    use crate::config;
+    use crate::factories::new_signature_provider;
+
+    #[test]
+    fn stamp_covered_by_signature() {
+        let config: config::SdkInfo = serde_json::from_slice(crate::CONFIG_BYTES.as_slice()).unwrap();
+        let sign = new_signature_provider(&config.signature).unwrap();
+
+        let stamp = Stamp::new(constants::LAYER_CICD.clone(), Some("pipeline-1234".to_string()));
+        let mut stamped = StampedAnnotation::new(mock_annotation(), stamp, &sign).unwrap();
+
+        let value = serde_json::to_value(&stamped).unwrap();
+        assert_eq!(value["layer"], "cicd");
+        assert_eq!(value["tag"], "pipeline-1234");
+
+        let signature = hex::decode(&stamped.annotation.signature).unwrap();
+        stamped.annotation.with_signature("");
+        assert!(sign.verify(&serde_json::to_vec(&stamped).unwrap(), &signature).unwrap());
+
+        stamped.stamp.tag = Some("pipeline-5678".to_string());
+        assert!(!sign.verify(&serde_json::to_vec(&stamped).unwrap(), &signature).unwrap());
+    }
+
+    #[test]
+    fn context_covered_by_signature() {
+        let config: config::SdkInfo = serde_json::from_slice(crate::CONFIG_BYTES.as_slice()).unwrap();
+        let sign = new_signature_provider(&config.signature).unwrap();
+
+        let context = [("containerId".to_string(), "3f4e8a1c".to_string())].into_iter().collect();
+        let mut stamped = StampedAnnotation::with_context(mock_annotation(), config.stamp(), context, &sign).unwrap();
+        assert_eq!(serde_json::to_value(&stamped).unwrap()["context"]["containerId"], "3f4e8a1c");
+
+        let signature = hex::decode(&stamped.annotation.signature).unwrap();
+        stamped.annotation.with_signature("");
+        assert!(sign.verify(&serde_json::to_vec(&stamped).unwrap(), &signature).unwrap());
+
+        stamped.context.insert("containerId".to_string(), "0000".to_string());
+        assert!(!sign.verify(&serde_json::to_vec(&stamped).unwrap(), &signature).unwrap());
+    }
+}
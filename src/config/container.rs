@@ -0,0 +1,45 @@
+use serde::{Serialize, Deserialize};
+
+fn id_env() -> String {
+    "CONTAINER_ID".to_string()
+}
+fn digest_env() -> String {
+    "IMAGE_DIGEST".to_string()
+}
+fn require_container() -> bool {
+    true
+}
+
+/// Settings for the container annotator
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContainerInfo {
+    /// Approved image digests (`sha256:<hex>`, optionally prefixed by `<repository>@`). When
+    /// empty, any image is accepted
+    #[serde(rename="allowList", default)]
+    pub allow_list: Vec<String>,
+    /// Environment variable holding the container ID
+    #[serde(rename="idEnv", default = "id_env")]
+    pub id_env: String,
+    /// Environment variable holding the image digest or image reference
+    #[serde(rename="digestEnv", default = "digest_env")]
+    pub digest_env: String,
+    /// Mounted metadata file (e.g. a Kubernetes downward API volume) the image digest is read
+    /// from when the environment does not provide it
+    #[serde(rename="metadataPath", default)]
+    pub metadata_path: Option<String>,
+    /// Leave the annotation unsatisfied when the process is not in a container
+    #[serde(rename="requireContainer", default = "require_container")]
+    pub require_container: bool,
+}
+
+impl Default for ContainerInfo {
+    fn default() -> Self {
+        ContainerInfo {
+            allow_list: Vec::new(),
+            id_env: id_env(),
+            digest_env: digest_env(),
+            metadata_path: None,
+            require_container: require_container(),
+        }
+    }
+}
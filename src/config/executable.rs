@@ -0,0 +1,28 @@
+use serde::{Serialize, Deserialize};
+
+fn include_libraries() -> bool {
+    true
+}
+
+/// Approved build digests for the executable annotator, computed with the configured hash type
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExecutableInfo {
+    #[serde(rename="allowList", default)]
+    pub allow_list: Vec<String>,
+    /// File of further approved digests, one per line
+    #[serde(rename="allowListPath", default)]
+    pub allow_list_path: Option<String>,
+    /// Also require every loaded shared library to be approved
+    #[serde(default = "include_libraries")]
+    pub libraries: bool,
+}
+
+impl Default for ExecutableInfo {
+    fn default() -> Self {
+        ExecutableInfo {
+            allow_list: Vec::new(),
+            allow_list_path: None,
+            libraries: include_libraries(),
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use serde::{Serialize, Deserialize};
+
+fn timestamp_field() -> String {
+    "timestamp".to_string()
+}
+fn nonce_field() -> String {
+    "nonce".to_string()
+}
+fn window_seconds() -> u64 {
+    300
+}
+fn cache_size() -> usize {
+    10_000
+}
+
+/// Settings for the freshness annotator. The timestamp and nonce are looked up by name in the
+/// protected payload envelope headers first, then in the payload content if it is a JSON object
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FreshnessInfo {
+    #[serde(rename="timestampField", default = "timestamp_field")]
+    pub timestamp_field: String,
+    #[serde(rename="nonceField", default = "nonce_field")]
+    pub nonce_field: String,
+    /// Largest accepted difference, either way, between the payload timestamp and local time
+    #[serde(rename="windowSeconds", default = "window_seconds")]
+    pub window_seconds: u64,
+    /// Most nonces remembered at once. Each is forgotten once its timestamp leaves the window,
+    /// while the cache is full, payloads with new nonces are not fresh
+    #[serde(rename="cacheSize", default = "cache_size")]
+    pub cache_size: usize,
+    /// File the seen nonces are persisted to, kept in memory only if not set
+    #[serde(rename="cachePath", default)]
+    pub cache_path: Option<String>,
+}
+
+impl Default for FreshnessInfo {
+    fn default() -> Self {
+        FreshnessInfo {
+            timestamp_field: timestamp_field(),
+            nonce_field: nonce_field(),
+            window_seconds: window_seconds(),
+            cache_size: cache_size(),
+            cache_path: None,
+        }
+    }
+}
@@ -0,0 +1,24 @@
+use serde::{Serialize, Deserialize};
+
+fn machine_id_path() -> String {
+    "/etc/machine-id".to_string()
+}
+
+/// Where the host identity recorded in annotations comes from
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum HostIdentityInfo {
+    /// The system hostname
+    #[default]
+    Hostname,
+    /// Contents of a machine ID file
+    MachineId {
+        #[serde(default = "machine_id_path")]
+        path: String,
+    },
+    /// A decentralised identifier, which must start with `did:`
+    Did { value: String },
+    /// Subject of the first certificate in a PEM file
+    Certificate { path: String },
+    Fixed { value: String },
+}
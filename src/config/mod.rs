@@ -1,18 +1,32 @@
+mod container;
+mod executable;
+mod freshness;
 mod hash;
+mod identity;
+mod payload;
+mod schema;
 mod sdk;
 mod sign;
 mod stream;
+mod tls;
 
+pub use container::*;
+pub use executable::*;
+pub use freshness::*;
 pub use hash::*;
+pub use identity::*;
+pub use payload::*;
+pub use schema::*;
 pub use sdk::*;
 pub use sign::*;
 pub use stream::*;
+pub use tls::*;
 
 
 
 #[cfg(test)]
 mod make_config_tests {
-    use super::{SdkInfo, StreamInfo, IotaStreamsConfig, MqttStreamConfig};
+    use super::{ActionAnnotators, AmqpExchangeType, SdkInfo, StreamConfig, StreamInfo, IotaStreamsConfig, KafkaKey, MqttStreamConfig, MqttVersion};
     #[test]
     fn new_config() {
         let config: SdkInfo = serde_json::from_slice(crate::CONFIG_BYTES.as_slice()).unwrap();
@@ -21,6 +35,19 @@ mod make_config_tests {
         assert!(config.annotators[0].is_base_annotation_type());
     }
 
+    #[test]
+    fn action_annotators_config() {
+        let mut config: SdkInfo = serde_json::from_slice(crate::CONFIG_BYTES.as_slice()).unwrap();
+        assert!(config.actions.is_empty());
+
+        let actions: ActionAnnotators = serde_json::from_str(r#"{"create": ["pki", "source"], "transit": ["tls"]}"#).unwrap();
+        assert!(actions.mutate.is_none());
+        assert!(actions.validate(&config.annotators));
+
+        config.annotators.retain(|kind| kind.kind() != "tls");
+        assert!(!actions.validate(&config.annotators));
+    }
+
     #[test]
     fn iota_streams_config() {
         let config: StreamInfo = serde_json::from_slice(crate::IOTA_TEST_CONFIG_BYTES.as_slice()).unwrap();
@@ -36,4 +63,108 @@ mod make_config_tests {
         assert!(config.stream_type.is_base_stream_type());
         assert!(matches!(config.config, _mqtt_config));
     }
+
+    #[test]
+    fn mqtt_v5_stream_config() {
+        let config: StreamInfo = serde_json::from_slice(crate::MQTT_V5_TEST_CONFIG_BYTES.as_slice()).unwrap();
+        match config.config {
+            StreamConfig::MQTT(mqtt) => {
+                assert_eq!(mqtt.version, MqttVersion::V5);
+                assert_eq!(mqtt.v5.message_expiry_secs, Some(3600));
+                assert_eq!(mqtt.v5.topic_aliases, 1);
+                assert_eq!(mqtt.v5.content_type, "application/json");
+                assert!(mqtt.tls.is_none());
+            },
+            _ => panic!("expected an mqtt stream config"),
+        }
+
+        // Existing configs stay on 3.1.1
+        let config: StreamInfo = serde_json::from_slice(crate::MQTT_TEST_CONFIG_BYTES.as_slice()).unwrap();
+        assert!(matches!(config.config, StreamConfig::MQTT(mqtt) if mqtt.version == MqttVersion::V311));
+    }
+
+    #[test]
+    fn kafka_stream_config() {
+        let config: StreamInfo = serde_json::from_slice(crate::KAFKA_TEST_CONFIG_BYTES.as_slice()).unwrap();
+        assert_eq!(config.stream_type, *crate::annotations::constants::STREAM_KAFKA);
+        match config.config {
+            StreamConfig::Kafka(kafka) => {
+                assert_eq!(kafka.brokers, vec!["localhost:9092".to_string()]);
+                assert_eq!(kafka.key, KafkaKey::AnnotationKey);
+                assert!(kafka.tls.is_none() && kafka.sasl.is_none());
+            },
+            _ => panic!("expected a kafka stream config"),
+        }
+    }
+
+    #[test]
+    fn nats_stream_config() {
+        let config: StreamInfo = serde_json::from_slice(crate::NATS_TEST_CONFIG_BYTES.as_slice()).unwrap();
+        assert_eq!(config.stream_type, *crate::annotations::constants::STREAM_NATS);
+        match config.config {
+            StreamConfig::Nats(nats) => {
+                assert_eq!(nats.subject_prefix, "alvarium");
+                assert!(nats.jetstream.is_none());
+            },
+            _ => panic!("expected a nats stream config"),
+        }
+    }
+
+    #[test]
+    fn http_stream_config() {
+        let config: StreamInfo = serde_json::from_slice(crate::HTTP_TEST_CONFIG_BYTES.as_slice()).unwrap();
+        assert_eq!(config.stream_type, *crate::annotations::constants::STREAM_HTTP);
+        match config.config {
+            StreamConfig::Http(http) => {
+                assert_eq!(http.bearer_token(), Some("A bearer token"));
+                assert!(http.signature.is_some());
+                assert_eq!((http.retries, http.batch_size), (3, 1));
+            },
+            _ => panic!("expected an http stream config"),
+        }
+    }
+
+    #[test]
+    fn amqp_stream_config() {
+        let config: StreamInfo = serde_json::from_slice(crate::AMQP_TEST_CONFIG_BYTES.as_slice()).unwrap();
+        assert_eq!(config.stream_type, *crate::annotations::constants::STREAM_AMQP);
+        match config.config {
+            StreamConfig::Amqp(amqp) => {
+                assert_eq!(amqp.exchange, "alvarium");
+                assert_eq!(amqp.exchange_type, AmqpExchangeType::Topic);
+                assert!(amqp.confirms && amqp.persistent);
+            },
+            _ => panic!("expected an amqp stream config"),
+        }
+    }
+
+    #[test]
+    fn redis_stream_config() {
+        let config: StreamInfo = serde_json::from_slice(crate::REDIS_TEST_CONFIG_BYTES.as_slice()).unwrap();
+        assert_eq!(config.stream_type, *crate::annotations::constants::STREAM_REDIS);
+        match config.config {
+            StreamConfig::Redis(redis) => {
+                assert_eq!(redis.max_len, Some(10000));
+                assert_eq!(redis.fields.content, "annotations");
+                assert_eq!(redis.fields.action, "action");
+            },
+            _ => panic!("expected a redis stream config"),
+        }
+    }
+
+    #[test]
+    fn coap_stream_config() {
+        let config: StreamInfo = serde_json::from_slice(crate::COAP_TEST_CONFIG_BYTES.as_slice()).unwrap();
+        assert_eq!(config.stream_type, *crate::annotations::constants::STREAM_COAP);
+        match config.config {
+            StreamConfig::Coap(coap) => {
+                assert!(coap.is_secure());
+                assert_eq!(coap.resource, "alvarium/annotations");
+                assert_eq!(coap.dtls.unwrap().identity, "alvarium-sdk");
+                assert!(coap.confirmable);
+                assert_eq!((coap.ack_timeout_ms, coap.max_retransmit), (2000, 4));
+            },
+            _ => panic!("expected a coap stream config"),
+        }
+    }
 }
@@ -0,0 +1,28 @@
+use serde::{Serialize, Deserialize};
+
+fn all_formats() -> Vec<PayloadFormat> {
+    vec![PayloadFormat::Signable, PayloadFormat::Jws, PayloadFormat::Cose, PayloadFormat::Detached]
+}
+
+/// Payload formats annotators recognise, tried in the order listed. Data in none of the listed
+/// formats is annotated as raw bytes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PayloadInfo {
+    #[serde(default = "all_formats")]
+    pub formats: Vec<PayloadFormat>,
+}
+
+impl Default for PayloadInfo {
+    fn default() -> Self {
+        PayloadInfo { formats: all_formats() }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PayloadFormat {
+    Signable,
+    Jws,
+    Cose,
+    Detached,
+}
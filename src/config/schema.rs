@@ -0,0 +1,16 @@
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+
+/// JSON Schemas the schema annotator validates payloads against. When `messageTypeField` is set
+/// and the payload holds a string in that field, the schema registered for that message type in
+/// `schemas` is used, otherwise the default schema at `path`
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SchemaInfo {
+    #[serde(default)]
+    pub path: Option<String>,
+    #[serde(rename="messageTypeField", default)]
+    pub message_type_field: Option<String>,
+    /// Schema file paths keyed by message type
+    #[serde(default)]
+    pub schemas: HashMap<String, String>,
+}
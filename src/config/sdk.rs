@@ -1,6 +1,7 @@
 use serde::{Serialize, Deserialize};
-use crate::config::{HashInfo, SignatureInfo, StreamInfo};
-use crate::annotations::constants::AnnotationType;
+use crate::config::{ContainerInfo, ExecutableInfo, FreshnessInfo, HashInfo, HostIdentityInfo, PayloadInfo, SchemaInfo, SignatureInfo, StreamInfo, TlsInfo};
+use crate::annotations::constants::{AnnotationType, LayerType, ANNOTATION_LINEAGE, LAYER_APP};
+use crate::annotations::Stamp;
 
 
 fn level_info() -> String {
@@ -9,6 +10,9 @@ fn level_info() -> String {
 fn debug_location() -> String {
     "log.out".to_string()
 }
+fn layer_app() -> LayerType {
+    LAYER_APP.clone()
+}
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SdkInfo {
@@ -18,6 +22,68 @@ pub struct SdkInfo {
     pub stream: StreamInfo,
     #[serde(default)]
     pub logging: LoggingConfiguration,
+    #[serde(default)]
+    pub tls: Option<TlsInfo>,
+    #[serde(default)]
+    pub actions: ActionAnnotators,
+    /// One of app, cicd or host, checked when the SDK is created
+    #[serde(default = "layer_app")]
+    pub layer: LayerType,
+    #[serde(default)]
+    pub tag: Option<String>,
+    #[serde(default)]
+    pub payload: PayloadInfo,
+    #[serde(default)]
+    pub freshness: FreshnessInfo,
+    #[serde(default)]
+    pub schema: SchemaInfo,
+    #[serde(default)]
+    pub executable: ExecutableInfo,
+    #[serde(default)]
+    pub container: ContainerInfo,
+    #[serde(default)]
+    pub host: HostIdentityInfo,
+    /// Number of threads synchronous annotators run on, so the annotators of one SDK call run in
+    /// parallel. 0 uses one thread per CPU, 1 runs them one after another on the calling task
+    #[serde(default)]
+    pub concurrency: usize,
+}
+
+impl SdkInfo {
+    /// The layer and tag stamped onto annotations unless overridden for a single call
+    pub fn stamp(&self) -> Stamp {
+        Stamp::new(self.layer.clone(), self.tag.clone())
+    }
+}
+
+/// Annotation types to run for each SDK action. An action that is not listed runs every
+/// configured annotator
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActionAnnotators {
+    #[serde(default)]
+    pub create: Option<Vec<AnnotationType>>,
+    #[serde(default)]
+    pub mutate: Option<Vec<AnnotationType>>,
+    #[serde(default)]
+    pub transit: Option<Vec<AnnotationType>>,
+    #[serde(default)]
+    pub publish: Option<Vec<AnnotationType>>,
+}
+
+impl ActionAnnotators {
+    pub fn is_empty(&self) -> bool {
+        self.create.is_none() && self.mutate.is_none() && self.transit.is_none() && self.publish.is_none()
+    }
+
+    /// Checks that every selected annotation type is one of the configured annotators. Lineage is
+    /// annotated by the SDK itself on mutate and cannot be selected
+    pub fn validate(&self, annotators: &[AnnotationType]) -> bool {
+        [&self.create, &self.mutate, &self.transit, &self.publish]
+            .into_iter()
+            .flatten()
+            .flatten()
+            .all(|kind| kind != &*ANNOTATION_LINEAGE && annotators.contains(kind))
+    }
 }
 
 #[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
@@ -7,6 +7,10 @@ pub struct SignatureInfo {
     pub public_key_info: KeyInfo,
     #[serde(rename="private")]
     pub private_key_info: KeyInfo,
+    /// JSON file or directory of trusted producer keys, looked up by the key ID carried in a
+    /// signed payload
+    #[serde(default)]
+    pub keyring: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
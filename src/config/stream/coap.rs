@@ -0,0 +1,55 @@
+use serde::{Serialize, Deserialize};
+use crate::config::UrlInfo;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CoapStreamConfig {
+    /// The server, with protocol `coap`, or `coaps` to connect over DTLS
+    pub provider: UrlInfo,
+    /// Path of the resource messages are POSTed to, e.g. `alvarium/annotations`
+    pub resource: String,
+    /// Sends confirmable messages, retransmitted until the server acknowledges them. Otherwise
+    /// messages are sent once and not acknowledged, saving power on lossy links. Payloads too
+    /// large for one datagram are sent block-wise, which needs confirmable messages
+    #[serde(default = "enabled")]
+    pub confirmable: bool,
+    /// Initial retransmission timeout, doubled on each retransmission
+    #[serde(rename="ackTimeoutMs", default = "ack_timeout_ms")]
+    pub ack_timeout_ms: u64,
+    #[serde(rename="maxRetransmit", default = "max_retransmit")]
+    pub max_retransmit: u32,
+    /// Pre-shared key for `coaps`
+    #[serde(default)]
+    pub dtls: Option<CoapDtlsConfig>,
+}
+
+impl CoapStreamConfig {
+    pub fn is_secure(&self) -> bool {
+        self.provider.protocol == "coaps"
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CoapDtlsConfig {
+    pub identity: String,
+    /// Hex encoded pre-shared key
+    key: String,
+}
+
+impl CoapDtlsConfig {
+    pub(crate) fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+fn enabled() -> bool {
+    true
+}
+
+// Defaults from RFC 7252 section 4.8
+fn ack_timeout_ms() -> u64 {
+    2000
+}
+
+fn max_retransmit() -> u32 {
+    4
+}
@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+use crate::config::SignatureInfo;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HttpStreamConfig {
+    /// URLs every message is POSTed to
+    pub endpoints: Vec<String>,
+    /// Extra headers sent with every request
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    #[serde(rename="bearerToken", default)]
+    bearer_token: Option<String>,
+    #[serde(default)]
+    pub tls: Option<HttpTlsConfig>,
+    /// Keys to sign request bodies with, normally the same as the SDK `signature` section. The
+    /// hex signature is sent in the `X-Alvarium-Signature` header
+    #[serde(default)]
+    pub signature: Option<SignatureInfo>,
+    /// Attempts after the first one for requests failing with a connection error or a 429 or 5xx
+    /// status. Past them the batch is held and sent again on the next publish or close, while
+    /// batches refused with any other status are dropped
+    #[serde(default = "retries")]
+    pub retries: u32,
+    /// Delay before the first retry, doubled for each one after up to 30 seconds
+    #[serde(rename="backoffMs", default = "backoff_ms")]
+    pub backoff_ms: u64,
+    /// Messages sent per request. Above 1 the body is a JSON array and a partial batch is held
+    /// until it fills or the publisher is closed, 0 is treated as 1
+    #[serde(rename="batchSize", default = "batch_size")]
+    pub batch_size: usize,
+    /// Messages held while a batch is still owed to an endpoint failing with a retryable error,
+    /// past which publishing fails rather than growing the buffer
+    #[serde(rename="maxBuffered", default = "max_buffered")]
+    pub max_buffered: usize,
+    #[serde(rename="timeoutMs", default = "timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+impl HttpStreamConfig {
+    pub(crate) fn bearer_token(&self) -> Option<&str> {
+        self.bearer_token.as_deref()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HttpTlsConfig {
+    /// CA bundle to verify the endpoints with, in addition to the system roots
+    #[serde(rename="caPath", default)]
+    pub ca_path: Option<String>,
+    /// Client certificate and key, for endpoints requiring mutual TLS
+    #[serde(rename="certPath", default)]
+    pub cert_path: Option<String>,
+    #[serde(rename="keyPath", default)]
+    pub key_path: Option<String>,
+}
+
+fn retries() -> u32 {
+    3
+}
+
+fn backoff_ms() -> u64 {
+    200
+}
+
+fn batch_size() -> usize {
+    1
+}
+
+fn max_buffered() -> usize {
+    1000
+}
+
+fn timeout_ms() -> u64 {
+    10000
+}
@@ -0,0 +1,82 @@
+use serde::{Serialize, Deserialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KafkaStreamConfig {
+    /// Bootstrap servers as `host:port`
+    pub brokers: Vec<String>,
+    pub topic: String,
+    #[serde(rename="clientId", default)]
+    pub client_id: Option<String>,
+    #[serde(default)]
+    pub key: KafkaKey,
+    /// Broker acknowledgements required per message, `0`, `1` or `all`
+    #[serde(default = "acks_all")]
+    pub acks: String,
+    #[serde(default = "enabled")]
+    pub idempotence: bool,
+    /// How long the producer waits to batch messages before sending
+    #[serde(rename="lingerMs", default = "linger_ms")]
+    pub linger_ms: u64,
+    /// How long a publish waits for delivery to be acknowledged
+    #[serde(rename="timeoutMs", default = "timeout_ms")]
+    pub timeout_ms: u64,
+    #[serde(default)]
+    pub tls: Option<KafkaTlsConfig>,
+    #[serde(default)]
+    pub sasl: Option<KafkaSaslConfig>,
+}
+
+/// What each record is keyed by, which decides the partition it lands in
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum KafkaKey {
+    /// Unkeyed, records are spread across partitions
+    None,
+    /// The key of the annotated data, so every annotation of the same data is kept in order
+    #[default]
+    AnnotationKey,
+    /// The SDK action the annotations were published for
+    Action,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KafkaTlsConfig {
+    /// CA bundle to verify the brokers with, the system roots if not set
+    #[serde(rename="caPath", default)]
+    pub ca_path: Option<String>,
+    /// Client certificate and key, for brokers requiring mutual TLS
+    #[serde(rename="certPath", default)]
+    pub cert_path: Option<String>,
+    #[serde(rename="keyPath", default)]
+    pub key_path: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KafkaSaslConfig {
+    /// `PLAIN`, `SCRAM-SHA-256` or `SCRAM-SHA-512`
+    pub mechanism: String,
+    pub username: String,
+    password: String,
+}
+
+impl KafkaSaslConfig {
+    pub(crate) fn password(&self) -> &str {
+        &self.password
+    }
+}
+
+fn acks_all() -> String {
+    "all".to_string()
+}
+
+fn enabled() -> bool {
+    true
+}
+
+fn linger_ms() -> u64 {
+    5
+}
+
+fn timeout_ms() -> u64 {
+    5000
+}
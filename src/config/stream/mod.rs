@@ -1,14 +1,26 @@
+mod amqp;
+mod coap;
+mod http;
 mod iota_streams;
+mod kafka;
 mod mqtt;
+mod nats;
+mod redis_streams;
 
 use alvarium_annotator::{SignProvider, StreamConfigWrapper};
+pub use amqp::*;
+pub use coap::*;
+pub use http::*;
 pub use iota_streams::*;
+pub use kafka::*;
 pub use mqtt::*;
+pub use nats::*;
+pub use redis_streams::*;
 
 use serde::{Serialize, Deserialize};
 
 use crate::annotations::constants::StreamType;
-use crate::providers::sign_provider::SignatureProviderWrap;
+use crate::providers::sign_provider::{Keyring, SignatureProviderWrap};
 use crate::errors::{Error, Result};
 
 
@@ -43,18 +55,32 @@ impl UrlInfo {
 pub enum StreamConfig {
     IotaStreams(IotaStreamsConfig),
     MQTT(MqttStreamConfig),
+    Kafka(KafkaStreamConfig),
+    Nats(NatsStreamConfig),
+    Http(HttpStreamConfig),
+    Amqp(AmqpStreamConfig),
+    Redis(RedisStreamConfig),
+    Coap(CoapStreamConfig),
 }
 
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Signable {
     pub seed: String,
-    pub signature: String
+    pub signature: String,
+    /// ID of the signing key in the verifier's keyring
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kid: Option<String>,
 }
 
 impl Signable {
     pub fn new(seed: String, signature: String) -> Self {
-        Signable { seed, signature }
+        Signable { seed, signature, kid: None }
+    }
+
+    pub fn with_key_id(mut self, kid: &str) -> Self {
+        self.kid = Some(kid.to_string());
+        self
     }
 
     pub fn verify_signature(&self, provider: &SignatureProviderWrap) -> Result<bool> {
@@ -70,6 +96,25 @@ impl Signable {
         }
     }
 
+    /// Verifies against the keyring key named by `kid`, or the SDK's own key when there is no
+    /// `kid`. A key ID the keyring does not hold never verifies
+    pub fn verify_with_keyring(&self, keyring: &Keyring, provider: &SignatureProviderWrap) -> Result<bool> {
+        if self.signature.is_empty() {
+            return Err(Error::EmptySignature)
+        }
+
+        let sig_bytes = hex::decode(&self.signature)?;
+        // The signable names no algorithm, the signing key decides it
+        let algorithm = match &self.kid {
+            Some(kid) => match keyring.get(kid) {
+                Some(key) => key.algorithm(),
+                None => return Ok(false),
+            },
+            None => "EdDSA",
+        };
+        keyring.verify(self.kid.as_deref(), provider, algorithm, self.seed.as_bytes(), &sig_bytes)
+    }
+
     pub fn to_bytes(&self) -> Vec<u8> {
         // Strings should not fail to serde
         // TODO: Verify that this is the case
@@ -81,7 +126,8 @@ impl Signable {
 #[cfg(test)]
 mod config_tests {
     use crypto::signatures::ed25519::SecretKey;
-    use crate::providers::sign_provider::{Ed25519Provider, SignatureProviderWrap};
+    use p256::ecdsa::{signature::Signer, SigningKey};
+    use crate::providers::sign_provider::{Ed25519Provider, Keyring, SignatureProviderWrap, VerifyingKey};
     use crate::config;
     use super::Signable;
     use alvarium_annotator::SignProvider;
@@ -96,7 +142,8 @@ mod config_tests {
 
         let signable = Signable {
             seed: data,
-            signature: sig
+            signature: sig,
+            kid: None,
         };
 
         assert!(signable.verify_signature(&sig_provider).unwrap())
@@ -112,11 +159,40 @@ mod config_tests {
 
         let signable = Signable {
             seed: data,
-            signature: hex::encode(raw_sig.to_bytes())
+            signature: hex::encode(raw_sig.to_bytes()),
+            kid: None,
         };
 
         let sig_provider = SignatureProviderWrap::Ed25519(Ed25519Provider::new(&config.signature).unwrap());
 
         assert!(!signable.verify_signature(&sig_provider).unwrap())
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn verify_signable_with_keyring() {
+        let config: config::SdkInfo = serde_json::from_slice(crate::CONFIG_BYTES.as_slice()).unwrap();
+        let sig_provider = SignatureProviderWrap::Ed25519(Ed25519Provider::new(&config.signature).unwrap());
+
+        let producer_key = SecretKey::generate().unwrap();
+        let gateway_key = SigningKey::from_slice(&[7u8; 32]).unwrap();
+        let mut keyring = Keyring::default();
+        keyring.insert("producer", VerifyingKey::from_hex(&hex::encode(producer_key.public_key().to_bytes())).unwrap());
+        keyring.insert("gateway", VerifyingKey::from_hex(&hex::encode(gateway_key.verifying_key().to_encoded_point(true).as_bytes())).unwrap());
+
+        let data = "A data packet to sign".to_string();
+        let producer_sig = hex::encode(producer_key.sign(data.as_bytes()).to_bytes());
+        let signable = Signable::new(data.clone(), producer_sig).with_key_id("producer");
+        assert!(signable.verify_with_keyring(&keyring, &sig_provider).unwrap());
+        // Not the SDK's key, nor any other in the keyring
+        assert!(!signable.verify_signature(&sig_provider).unwrap());
+        assert!(!signable.clone().with_key_id("gateway").verify_with_keyring(&keyring, &sig_provider).unwrap());
+        assert!(!Signable { kid: None, ..signable.clone() }.verify_with_keyring(&keyring, &sig_provider).unwrap());
+        // An unknown key ID fails closed, even for the SDK's own signature
+        let sdk_sig = sig_provider.sign(data.as_bytes()).unwrap();
+        assert!(!Signable::new(data.clone(), sdk_sig).with_key_id("unknown").verify_with_keyring(&keyring, &sig_provider).unwrap());
+
+        let gateway_sig: p256::ecdsa::Signature = gateway_key.sign(data.as_bytes());
+        let signable = Signable::new(data, hex::encode(gateway_sig.to_bytes())).with_key_id("gateway");
+        assert!(signable.verify_with_keyring(&keyring, &sig_provider).unwrap());
+    }
+}
@@ -12,13 +12,89 @@ pub struct MqttStreamConfig {
     pub qos: u8,
     pub user: String,
     password: String,
+    /// The broker, with protocol `tcp`, `tls` (or `ssl`/`mqtts`), `ws` or `wss`
     pub provider: UrlInfo,
     pub cleanness: bool,
-    pub topics: Vec<String>
+    pub topics: Vec<String>,
+    /// Trust and client authentication for `tls` and `wss`. Without it the broker is verified
+    /// against the Mozilla root certificates
+    #[serde(default)]
+    pub tls: Option<MqttTlsConfig>,
+    /// Path of the websocket endpoint for `ws` and `wss`
+    #[serde(rename="wsPath", default = "ws_path")]
+    pub ws_path: String,
+    #[serde(default)]
+    pub version: MqttVersion,
+    /// Publish properties, only sent when `version` is `5`
+    #[serde(default)]
+    pub v5: MqttV5Config,
 }
 
 impl MqttStreamConfig {
     pub(crate) fn password(&self) -> &str {
         &self.password
     }
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MqttTlsConfig {
+    /// PEM bundle of CA certificates trusted in place of the Mozilla roots
+    #[serde(rename="caPath", default)]
+    pub ca_path: Option<String>,
+    /// PEM certificate chain and private key for mutual TLS, both or neither
+    #[serde(rename="certPath", default)]
+    pub cert_path: Option<String>,
+    #[serde(rename="keyPath", default)]
+    pub key_path: Option<String>,
+    /// ALPN protocols offered to the broker, e.g. `x-amzn-mqtt-ca` for AWS IoT on port 443
+    #[serde(default)]
+    pub alpn: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum MqttVersion {
+    #[default]
+    #[serde(rename="3.1.1")]
+    V311,
+    #[serde(rename="5")]
+    V5,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MqttV5Config {
+    /// Seconds the broker holds an undelivered message before discarding it, no expiry if unset
+    #[serde(rename="messageExpirySecs", default)]
+    pub message_expiry_secs: Option<u32>,
+    /// Topic aliases to use, one per topic up to the broker's maximum. 0 sends the full topic
+    /// with every message
+    #[serde(rename="topicAliases", default)]
+    pub topic_aliases: u16,
+    #[serde(rename="contentType", default = "content_type")]
+    pub content_type: String,
+    /// How long a publish waits for the broker to acknowledge it on every topic
+    #[serde(rename="timeoutMs", default = "timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+impl Default for MqttV5Config {
+    fn default() -> Self {
+        MqttV5Config {
+            message_expiry_secs: None,
+            topic_aliases: 0,
+            content_type: content_type(),
+            timeout_ms: timeout_ms(),
+        }
+    }
+}
+
+fn content_type() -> String {
+    "application/json".to_string()
+}
+
+fn timeout_ms() -> u64 {
+    5000
+}
+
+fn ws_path() -> String {
+    "/mqtt".to_string()
+}
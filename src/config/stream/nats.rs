@@ -0,0 +1,55 @@
+use serde::{Serialize, Deserialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NatsStreamConfig {
+    /// Server URLs, e.g. `nats://localhost:4222`
+    pub servers: Vec<String>,
+    /// Messages are published to `<subjectPrefix>.<action>`, e.g. `alvarium.create`
+    #[serde(rename="subjectPrefix", default = "subject_prefix")]
+    pub subject_prefix: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub user: Option<String>,
+    #[serde(default)]
+    password: Option<String>,
+    /// A `.creds` file holding the user JWT and NKey seed, taking precedence over `user`
+    #[serde(rename="credentialsPath", default)]
+    pub credentials_path: Option<String>,
+    #[serde(rename="tlsRequired", default)]
+    pub tls_required: bool,
+    /// CA bundle to verify the servers with, in addition to the system roots
+    #[serde(rename="caPath", default)]
+    pub ca_path: Option<String>,
+    /// Publishes through JetStream when set, waiting for the stream to acknowledge each message
+    #[serde(default)]
+    pub jetstream: Option<JetStreamConfig>,
+}
+
+impl NatsStreamConfig {
+    pub(crate) fn password(&self) -> Option<&str> {
+        self.password.as_deref()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JetStreamConfig {
+    /// Sets a message ID derived from the message contents, so the stream drops redelivered
+    /// copies within its duplicate window
+    #[serde(default = "enabled")]
+    pub dedupe: bool,
+    #[serde(rename="ackTimeoutMs", default = "ack_timeout_ms")]
+    pub ack_timeout_ms: u64,
+}
+
+fn subject_prefix() -> String {
+    "alvarium".to_string()
+}
+
+fn enabled() -> bool {
+    true
+}
+
+fn ack_timeout_ms() -> u64 {
+    5000
+}
@@ -0,0 +1,60 @@
+use serde::{Serialize, Deserialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RedisStreamConfig {
+    /// Server URL, e.g. `redis://localhost:6379`, or `rediss://` for TLS
+    url: String,
+    /// Key of the stream messages are added to
+    pub stream: String,
+    /// Trims the stream to about this many entries on every add
+    #[serde(rename="maxLen", default)]
+    pub max_len: Option<usize>,
+    /// Lets Redis trim lazily to whole macro nodes, which is much cheaper than an exact length
+    #[serde(rename="approximateTrim", default = "enabled")]
+    pub approximate_trim: bool,
+    #[serde(default)]
+    pub fields: RedisFieldMapping,
+}
+
+impl RedisStreamConfig {
+    pub(crate) fn url(&self) -> &str {
+        &self.url
+    }
+}
+
+/// Names of the stream entry fields each part of a message is written to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RedisFieldMapping {
+    #[serde(default = "action_field")]
+    pub action: String,
+    #[serde(rename="messageType", default = "message_type_field")]
+    pub message_type: String,
+    #[serde(default = "content_field")]
+    pub content: String,
+}
+
+impl Default for RedisFieldMapping {
+    fn default() -> Self {
+        RedisFieldMapping {
+            action: action_field(),
+            message_type: message_type_field(),
+            content: content_field(),
+        }
+    }
+}
+
+fn enabled() -> bool {
+    true
+}
+
+fn action_field() -> String {
+    "action".to_string()
+}
+
+fn message_type_field() -> String {
+    "type".to_string()
+}
+
+fn content_field() -> String {
+    "content".to_string()
+}
@@ -0,0 +1,39 @@
+use serde::{Serialize, Deserialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TlsInfo {
+    #[serde(default)]
+    pub cert: Option<String>,
+    #[serde(default)]
+    pub key: Option<String>,
+    #[serde(default)]
+    pub policy: TlsPolicyInfo,
+}
+
+/// Requirements a TLS session must meet for a TLS annotation to be satisfied. Every field is
+/// optional, an empty policy only requires that the handshake has completed
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TlsPolicyInfo {
+    /// Lowest accepted protocol version, "1.2" or "1.3"
+    #[serde(rename="minVersion", default)]
+    pub min_version: Option<String>,
+    /// IANA names of the accepted cipher suites (e.g. TLS13_AES_256_GCM_SHA384)
+    #[serde(rename="cipherSuites", default)]
+    pub cipher_suites: Vec<String>,
+    /// Require the peer to have presented a certificate
+    #[serde(rename="requireMutual", default)]
+    pub require_mutual: bool,
+    /// Path to a PEM bundle the peer certificate chain must validate against
+    #[serde(rename="caBundle", default)]
+    pub ca_bundle: Option<String>,
+    /// Patterns matched against the peer certificate subject alternative names. For DNS names a
+    /// `*` may only appear in the left-most label and never matches across a `.`. URIs match
+    /// exactly, or under a prefix ending in `/*` for one more path segment or `/**` for any number.
+    /// Email addresses and IP addresses match exactly
+    #[serde(rename="sanPatterns", default)]
+    pub san_patterns: Vec<String>,
+    /// Base64 SHA-256 digests of accepted subject public key infos, optionally prefixed with
+    /// "sha256/"
+    #[serde(default)]
+    pub pins: Vec<String>,
+}
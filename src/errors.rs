@@ -15,12 +15,24 @@ pub enum Error {
     #[error("HTTP Client error: {0}")]
     HttpClientError(reqwest::Error),
 
+    #[error("HTTP endpoint {0} responded with status {1}")]
+    HttpEndpointError(String, u16),
+
+    #[error("HTTP publisher is already holding {0} undelivered messages")]
+    HttpBufferFull(usize),
+
     #[error("Core Alvarium error: {0}")]
     AlvariumCoreError(alvarium_annotator::Error),
 
     #[error("Not a pre known Alvarium annotator: {0}. Should be built separately")]
     NotKnownProvider(String),
 
+    #[error("Not an Alvarium layer: {0}. Should be app, cicd or host")]
+    UnknownLayer(String),
+
+    #[error("{0} annotations are made by the SDK itself and cannot be configured")]
+    ReservedAnnotationType(String),
+
     #[error("Streams Provider error: {0}")]
     StreamsError(streams::Error),
 
@@ -36,6 +48,12 @@ pub enum Error {
     #[error("Mqtt Connect Return error: {0}")]
     MqttConnectReturnError(String),
 
+    #[error("Mqtt v5 Client error: {0}")]
+    MqttV5ClientError(rumqttc::v5::ClientError),
+
+    #[error("Mqtt v5 Connection error: {0}")]
+    MqttV5ConnectionError(rumqttc::v5::ConnectionError),
+
     #[error("Did not find keyload, subscription may not have been processed correctly")]
     StreamsKeyloadNotFound,
 
@@ -61,7 +79,52 @@ pub enum Error {
     External(Box<dyn std::error::Error + Send + Sync>),
 
     #[error("Backup failed: {0}")]
-    BackupFailed(std::io::Error)
+    BackupFailed(std::io::Error),
+
+    #[error("Failed to load CA bundle: {0}")]
+    CaBundleError(std::io::Error),
+
+    #[error("Invalid certificate: {0}")]
+    InvalidCertificate(String),
+
+    #[error("Failed to load keyring: {0}")]
+    KeyringError(std::io::Error),
+
+    #[error("Failed to persist replay cache: {0}")]
+    ReplayCacheError(std::io::Error),
+
+    #[error("Invalid JSON schema: {0}")]
+    SchemaError(String),
+
+    #[error("Could not resolve host identity: {0}")]
+    HostIdentityError(String),
+
+    #[error("Annotator pool error: {0}")]
+    AnnotatorPoolError(String),
+
+    #[error("Stream type needs the crate built with the \"{0}\" feature")]
+    FeatureNotEnabled(&'static str),
+
+    #[cfg(feature = "kafka")]
+    #[error("Kafka error: {0}")]
+    KafkaError(rdkafka::error::KafkaError),
+
+    #[error("NATS error: {0}")]
+    NatsError(String),
+
+    #[cfg(feature = "amqp")]
+    #[error("AMQP error: {0}")]
+    AmqpError(lapin::Error),
+
+    #[error("Broker did not confirm the message published to {0}")]
+    PublishNotConfirmed(String),
+
+    #[cfg(feature = "redis")]
+    #[error("Redis error: {0}")]
+    RedisError(redis::RedisError),
+
+    #[error("CoAP error: {0}")]
+    CoapError(String),
 }
 
 impl From<serde_json::Error> for Error {
@@ -107,12 +170,51 @@ impl From<rumqttc::ConnectReturnCode> for Error {
     }
 }
 
+impl From<rumqttc::v5::ClientError> for Error {
+    fn from(e: rumqttc::v5::ClientError) -> Self {
+        Error::MqttV5ClientError(e)
+    }
+}
+
+impl From<rumqttc::v5::ConnectionError> for Error {
+    fn from(e: rumqttc::v5::ConnectionError) -> Self {
+        Error::MqttV5ConnectionError(e)
+    }
+}
+
+impl From<rumqttc::v5::mqttbytes::v5::ConnectReturnCode> for Error {
+    fn from(e: rumqttc::v5::mqttbytes::v5::ConnectReturnCode) -> Self {
+        Error::MqttConnectReturnError(format!("{:?}", e))
+    }
+}
+
 impl From<reqwest::Error> for Error {
     fn from(e: reqwest::Error) -> Self {
         Error::HttpClientError(e)
     }
 }
 
+#[cfg(feature = "kafka")]
+impl From<rdkafka::error::KafkaError> for Error {
+    fn from(e: rdkafka::error::KafkaError) -> Self {
+        Error::KafkaError(e)
+    }
+}
+
+#[cfg(feature = "amqp")]
+impl From<lapin::Error> for Error {
+    fn from(e: lapin::Error) -> Self {
+        Error::AmqpError(e)
+    }
+}
+
+#[cfg(feature = "redis")]
+impl From<redis::RedisError> for Error {
+    fn from(e: redis::RedisError) -> Self {
+        Error::RedisError(e)
+    }
+}
+
 impl From<streams::LetsError> for Error {
     fn from(e: streams::LetsError) -> Self {
         Error::StreamsLetsError(e)
@@ -1,12 +1,15 @@
-use alvarium_annotator::constants;
-use crate::SdkAnnotator;
-use crate::annotations::{PkiAnnotator, SourceAnnotator, TlsAnnotator, TpmAnnotator};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use rayon::ThreadPool;
+use crate::annotations::constants;
+use crate::{SdkAnnotator, SdkAsyncAnnotator};
+use crate::annotations::{ContainerAnnotator, ExecutableAnnotator, FreshnessAnnotator, PkiAnnotator, SchemaAnnotator, SourceAnnotator, SyncAnnotator, TlsAnnotator, TpmAnnotator};
 use crate::config::SdkInfo;
 use crate::errors::{Error, Result};
 
 
 pub fn new_annotator(kind: constants::AnnotationType, cfg: SdkInfo) -> Result<Box<SdkAnnotator>> {
-    if !kind.is_base_annotation_type() {
+    if !kind.is_base_annotation_type() && !constants::is_sdk_annotation_type(&kind) {
         return Err(Error::NotKnownProvider(kind.kind().to_string()))
     }
 
@@ -15,6 +18,49 @@ pub fn new_annotator(kind: constants::AnnotationType, cfg: SdkInfo) -> Result<Bo
         "pki" => Ok(Box::new(PkiAnnotator::new(&cfg)?)),
         "tls" => Ok(Box::new(TlsAnnotator::new(&cfg)?)),
         "tpm" => Ok(Box::new(TpmAnnotator::new(&cfg)?)),
+        "freshness" => Ok(Box::new(FreshnessAnnotator::new(&cfg)?)),
+        "schema" => Ok(Box::new(SchemaAnnotator::new(&cfg)?)),
+        "executable" => Ok(Box::new(ExecutableAnnotator::new(&cfg)?)),
+        "container" => Ok(Box::new(ContainerAnnotator::new(&cfg)?)),
+        // Lineage is annotated by the SDK on mutate, linking the old and new data
+        "lineage" => Err(Error::ReservedAnnotationType(kind.kind().to_string())),
         _ => Err(Error::NotKnownProvider(kind.kind().to_string()))
     }
-}
\ No newline at end of file
+}
+
+/// Same as [`new_annotator`], adapted to run alongside async annotators in the SDK
+pub fn new_async_annotator(kind: constants::AnnotationType, cfg: SdkInfo) -> Result<Box<SdkAsyncAnnotator>> {
+    Ok(SyncAnnotator::boxed(new_annotator(kind, cfg)?))
+}
+
+/// Same as [`new_async_annotator`], running on the annotator pool sized by `cfg.concurrency`
+/// so that annotators of one SDK call run in parallel. Annotators with the same concurrency share
+/// a pool
+pub fn new_pooled_annotator(kind: constants::AnnotationType, cfg: SdkInfo) -> Result<Box<SdkAsyncAnnotator>> {
+    if cfg.concurrency == 1 {
+        return new_async_annotator(kind, cfg)
+    }
+
+    let pool = new_annotator_pool(cfg.concurrency)?;
+    Ok(Box::new(SyncAnnotator::pooled(new_annotator(kind, cfg)?, pool)))
+}
+
+lazy_static! {
+    static ref ANNOTATOR_POOLS: Mutex<HashMap<usize, Arc<ThreadPool>>> = Mutex::new(HashMap::new());
+}
+
+fn new_annotator_pool(threads: usize) -> Result<Arc<ThreadPool>> {
+    let mut pools = ANNOTATOR_POOLS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some(pool) = pools.get(&threads) {
+        return Ok(pool.clone())
+    }
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .thread_name(|i| format!("alvarium-annotator-{}", i))
+        .build()
+        .map_err(|e| Error::AnnotatorPoolError(e.to_string()))?;
+    let pool = Arc::new(pool);
+    pools.insert(threads, pool.clone());
+    Ok(pool)
+}
@@ -0,0 +1,21 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use crate::config::HostIdentityInfo;
+use crate::errors::Result;
+use crate::providers::identity_provider::resolve_host_identity;
+
+lazy_static! {
+    static ref HOST_IDENTITIES: Mutex<HashMap<HostIdentityInfo, Arc<str>>> = Mutex::new(HashMap::new());
+}
+
+/// The host identity for `info`, resolved on first use and shared by every later caller
+pub fn new_host_identity(info: &HostIdentityInfo) -> Result<Arc<str>> {
+    let mut identities = HOST_IDENTITIES.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some(identity) = identities.get(info) {
+        return Ok(identity.clone())
+    }
+
+    let identity: Arc<str> = resolve_host_identity(info)?.into();
+    identities.insert(info.clone(), identity.clone());
+    Ok(identity)
+}
@@ -1,19 +1,25 @@
 mod stream_factory;
 mod annotator_factory;
 mod hash_factory;
+mod identity_factory;
+mod payload_factory;
 mod signature_factory;
 
 
 pub use stream_factory::*;
 pub use annotator_factory::*;
 pub use hash_factory::*;
+pub use identity_factory::*;
+pub use payload_factory::*;
 pub use signature_factory::*;
 
 
 #[cfg(test)]
 mod factory_tests {
+    use crate::annotations::constants::ANNOTATION_LINEAGE;
     use crate::config::SdkInfo;
-    use crate::factories::{new_annotator, new_hash_provider, new_signature_provider, new_stream_provider};
+    use crate::errors::Error;
+    use crate::factories::{new_annotator, new_hash_provider, new_host_identity, new_payload_extractor, new_signature_provider, new_stream_provider};
 
     #[tokio::test]
     async fn provider_factory() {
@@ -40,4 +46,30 @@ mod factory_tests {
             let _annotator = new_annotator(ann.clone(), sdk_info.clone()).unwrap();
         }
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn lineage_not_configurable() {
+        let mut sdk_info: SdkInfo = serde_json::from_slice(crate::CONFIG_BYTES.as_slice()).unwrap();
+        sdk_info.annotators.push(ANNOTATION_LINEAGE.clone());
+        sdk_info.actions = serde_json::from_str(r#"{"mutate": ["lineage"]}"#).unwrap();
+
+        assert!(matches!(new_annotator(ANNOTATION_LINEAGE.clone(), sdk_info.clone()), Err(Error::ReservedAnnotationType(_))));
+        assert!(!sdk_info.actions.validate(&sdk_info.annotators));
+    }
+
+    #[tokio::test]
+    async fn payload_factory() {
+        let sdk_info: SdkInfo = serde_json::from_slice(crate::CONFIG_BYTES.as_slice()).unwrap();
+        let extractor = new_payload_extractor(&sdk_info.payload);
+        let data = "Some random data".as_bytes();
+        assert_eq!(extractor.extract(data).content, data);
+    }
+
+    #[tokio::test]
+    async fn host_identity_factory() {
+        let sdk_info: SdkInfo = serde_json::from_slice(crate::CONFIG_BYTES.as_slice()).unwrap();
+        let identity = new_host_identity(&sdk_info.host).unwrap();
+        let cached = new_host_identity(&sdk_info.host).unwrap();
+        assert!(std::sync::Arc::ptr_eq(&identity, &cached));
+    }
+}
@@ -0,0 +1,19 @@
+use crate::config::{PayloadFormat, PayloadInfo};
+use crate::providers::payload_provider::{
+    CoseSign1Extractor, DetachedSignatureExtractor, JwsExtractor, PayloadExtractor, PayloadExtractorChain,
+    SignableExtractor,
+};
+
+pub fn new_payload_extractor(cfg: &PayloadInfo) -> PayloadExtractorChain {
+    let extractors = cfg.formats.iter()
+        .map(|format| -> Box<dyn PayloadExtractor + Send + Sync> {
+            match format {
+                PayloadFormat::Signable => Box::new(SignableExtractor),
+                PayloadFormat::Jws => Box::new(JwsExtractor),
+                PayloadFormat::Cose => Box::new(CoseSign1Extractor),
+                PayloadFormat::Detached => Box::new(DetachedSignatureExtractor),
+            }
+        })
+        .collect();
+    PayloadExtractorChain::new(extractors)
+}
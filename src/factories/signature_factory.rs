@@ -1,6 +1,6 @@
 use crate::config::SignatureInfo;
 use crate::errors::{Result, Error::NotKnownProvider};
-use crate::providers::sign_provider::{Ed25519Provider, SignatureProviderWrap};
+use crate::providers::sign_provider::{Ed25519Provider, Keyring, SignatureProviderWrap};
 
 
 pub fn new_signature_provider(config: &SignatureInfo) -> Result<SignatureProviderWrap> {
@@ -12,4 +12,12 @@ pub fn new_signature_provider(config: &SignatureInfo) -> Result<SignatureProvide
         "ed25519" => Ok(SignatureProviderWrap::Ed25519(Ed25519Provider::new(config)?)),
         _ => Err(NotKnownProvider(config.private_key_info.key_type.0.clone()))
     }
-}
\ No newline at end of file
+}
+
+/// The configured keyring of trusted producer keys, or an empty one if none is configured
+pub fn new_keyring(config: &SignatureInfo) -> Result<Keyring> {
+    match &config.keyring {
+        Some(path) => Keyring::load(path),
+        None => Ok(Keyring::default()),
+    }
+}
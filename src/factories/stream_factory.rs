@@ -1,7 +1,17 @@
 use alvarium_annotator::Publisher;
 use crate::errors::Result;
-use crate::config::{StreamConfig, StreamInfo};
-use crate::providers::stream_provider::{IotaPublisher, MqttPublisher, PublisherWrap};
+use crate::config::{MqttVersion, StreamConfig, StreamInfo};
+use crate::providers::stream_provider::{HttpPublisher, IotaPublisher, MqttPublisher, MqttV5Publisher, PublisherWrap};
+#[cfg(feature = "kafka")]
+use crate::providers::stream_provider::KafkaPublisher;
+#[cfg(feature = "nats")]
+use crate::providers::stream_provider::NatsPublisher;
+#[cfg(feature = "amqp")]
+use crate::providers::stream_provider::AmqpPublisher;
+#[cfg(feature = "redis")]
+use crate::providers::stream_provider::RedisPublisher;
+#[cfg(feature = "coap")]
+use crate::providers::stream_provider::CoapPublisher;
 
 
 pub async fn new_stream_provider(cfg: StreamInfo) -> Result<PublisherWrap> {
@@ -10,9 +20,52 @@ pub async fn new_stream_provider(cfg: StreamInfo) -> Result<PublisherWrap> {
             let publisher = IotaPublisher::new(&cfg).await?;
             Ok(PublisherWrap::Iota(publisher))
         }
+        StreamConfig::MQTT(ref mqtt) if mqtt.version == MqttVersion::V5 => {
+            let publisher = MqttV5Publisher::new(&cfg).await?;
+            Ok(PublisherWrap::MqttV5(publisher))
+        }
         StreamConfig::MQTT(_) => {
             let publisher = MqttPublisher::new(&cfg).await?;
             Ok(PublisherWrap::Mqtt(publisher))
         }
+        #[cfg(feature = "kafka")]
+        StreamConfig::Kafka(_) => {
+            let publisher = KafkaPublisher::new(&cfg).await?;
+            Ok(PublisherWrap::Kafka(publisher))
+        }
+        #[cfg(not(feature = "kafka"))]
+        StreamConfig::Kafka(_) => Err(crate::errors::Error::FeatureNotEnabled("kafka")),
+        #[cfg(feature = "nats")]
+        StreamConfig::Nats(_) => {
+            let publisher = NatsPublisher::new(&cfg).await?;
+            Ok(PublisherWrap::Nats(publisher))
+        }
+        #[cfg(not(feature = "nats"))]
+        StreamConfig::Nats(_) => Err(crate::errors::Error::FeatureNotEnabled("nats")),
+        StreamConfig::Http(_) => {
+            let publisher = HttpPublisher::new(&cfg).await?;
+            Ok(PublisherWrap::Http(publisher))
+        }
+        #[cfg(feature = "amqp")]
+        StreamConfig::Amqp(_) => {
+            let publisher = AmqpPublisher::new(&cfg).await?;
+            Ok(PublisherWrap::Amqp(publisher))
+        }
+        #[cfg(not(feature = "amqp"))]
+        StreamConfig::Amqp(_) => Err(crate::errors::Error::FeatureNotEnabled("amqp")),
+        #[cfg(feature = "redis")]
+        StreamConfig::Redis(_) => {
+            let publisher = RedisPublisher::new(&cfg).await?;
+            Ok(PublisherWrap::Redis(publisher))
+        }
+        #[cfg(not(feature = "redis"))]
+        StreamConfig::Redis(_) => Err(crate::errors::Error::FeatureNotEnabled("redis")),
+        #[cfg(feature = "coap")]
+        StreamConfig::Coap(_) => {
+            let publisher = CoapPublisher::new(&cfg).await?;
+            Ok(PublisherWrap::Coap(publisher))
+        }
+        #[cfg(not(feature = "coap"))]
+        StreamConfig::Coap(_) => Err(crate::errors::Error::FeatureNotEnabled("coap")),
     }
 }
\ No newline at end of file
@@ -8,7 +8,10 @@ pub mod factories;
 pub mod logging;
 pub mod errors;
 
-pub type SdkAnnotator = dyn alvarium_annotator::Annotator<Error = crate::errors::Error>;
+/// Annotators implementing only [`Annotator`](annotations::Annotator) are adapted with
+/// [`PlainAnnotator`](annotations::PlainAnnotator)
+pub type SdkAnnotator = dyn annotations::PayloadAnnotator + Send;
+pub type SdkAsyncAnnotator = dyn annotations::AsyncAnnotator;
 
 #[macro_use]
 extern crate lazy_static;
@@ -27,4 +30,25 @@ lazy_static! {
     pub static ref IOTA_TEST_CONFIG_BYTES: Vec<u8> = {
         std::fs::read("resources/iota_streams_config.json").unwrap()
     };
+    pub static ref KAFKA_TEST_CONFIG_BYTES: Vec<u8> = {
+        std::fs::read("resources/kafka_stream_config.json").unwrap()
+    };
+    pub static ref NATS_TEST_CONFIG_BYTES: Vec<u8> = {
+        std::fs::read("resources/nats_stream_config.json").unwrap()
+    };
+    pub static ref HTTP_TEST_CONFIG_BYTES: Vec<u8> = {
+        std::fs::read("resources/http_stream_config.json").unwrap()
+    };
+    pub static ref AMQP_TEST_CONFIG_BYTES: Vec<u8> = {
+        std::fs::read("resources/amqp_stream_config.json").unwrap()
+    };
+    pub static ref REDIS_TEST_CONFIG_BYTES: Vec<u8> = {
+        std::fs::read("resources/redis_stream_config.json").unwrap()
+    };
+    pub static ref MQTT_V5_TEST_CONFIG_BYTES: Vec<u8> = {
+        std::fs::read("resources/mqtt_v5_stream_config.json").unwrap()
+    };
+    pub static ref COAP_TEST_CONFIG_BYTES: Vec<u8> = {
+        std::fs::read("resources/coap_stream_config.json").unwrap()
+    };
 }
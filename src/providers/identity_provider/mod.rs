@@ -0,0 +1,81 @@
+use crate::config::HostIdentityInfo;
+use crate::errors::{Error, Result};
+
+/// Resolves the identity annotations are attributed to. This does the lookup every time it is
+/// called, annotators get a cached value through `factories::new_host_identity`
+pub fn resolve_host_identity(info: &HostIdentityInfo) -> Result<String> {
+    let identity = match info {
+        HostIdentityInfo::Hostname => gethostname::gethostname()
+            .into_string()
+            .map_err(|_| Error::NoHostName)?,
+        HostIdentityInfo::MachineId { path } => std::fs::read_to_string(path)
+            .map_err(|e| Error::HostIdentityError(format!("{}: {}", path, e)))?
+            .trim()
+            .to_string(),
+        HostIdentityInfo::Did { value } => match value.starts_with("did:") {
+            true => value.clone(),
+            false => return Err(Error::HostIdentityError(format!("not a DID: {}", value))),
+        },
+        HostIdentityInfo::Certificate { path } => certificate_subject(path)?,
+        HostIdentityInfo::Fixed { value } => value.clone(),
+    };
+
+    match identity.is_empty() {
+        true => Err(Error::HostIdentityError(format!("empty identity from {:?}", info))),
+        false => Ok(identity),
+    }
+}
+
+// Subject distinguished name of the first certificate in a PEM file
+fn certificate_subject(path: &str) -> Result<String> {
+    let pem = std::fs::read(path).map_err(|e| Error::HostIdentityError(format!("{}: {}", path, e)))?;
+    let der = rustls_pemfile::certs(&mut pem.as_slice())
+        .map_err(|e| Error::HostIdentityError(format!("{}: {}", path, e)))?
+        .into_iter()
+        .next()
+        .ok_or_else(|| Error::HostIdentityError(format!("{}: no certificate found", path)))?;
+    let (_, cert) = x509_parser::parse_x509_certificate(&der)
+        .map_err(|e| Error::InvalidCertificate(e.to_string()))?;
+    Ok(cert.subject().to_string())
+}
+
+
+#[cfg(test)]
+mod identity_tests {
+    use crate::config::HostIdentityInfo;
+    use super::resolve_host_identity;
+
+    #[test]
+    fn resolve_identities() {
+        let hostname = resolve_host_identity(&HostIdentityInfo::Hostname).unwrap();
+        assert_eq!(hostname, gethostname::gethostname().to_str().unwrap());
+
+        let fixed = HostIdentityInfo::Fixed { value: "collector-eu-1".to_string() };
+        assert_eq!(resolve_host_identity(&fixed).unwrap(), "collector-eu-1");
+
+        let did = HostIdentityInfo::Did { value: "did:iota:0xabc".to_string() };
+        assert_eq!(resolve_host_identity(&did).unwrap(), "did:iota:0xabc");
+
+        let path = std::env::temp_dir().join(format!("alvarium_machine_id_{}", ulid::Ulid::new()));
+        std::fs::write(&path, "b08dfa6083e7567a1921a715000001fb\n").unwrap();
+        let machine_id = HostIdentityInfo::MachineId { path: path.to_str().unwrap().to_string() };
+        let resolved = resolve_host_identity(&machine_id);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(resolved.unwrap(), "b08dfa6083e7567a1921a715000001fb");
+
+        let certificate = HostIdentityInfo::Certificate { path: "resources/test_certs/device.pem".to_string() };
+        assert_eq!(resolve_host_identity(&certificate).unwrap(), "CN=device.example.com");
+    }
+
+    #[test]
+    fn invalid_identities() {
+        let not_a_did = HostIdentityInfo::Did { value: "collector-eu-1".to_string() };
+        assert!(resolve_host_identity(&not_a_did).is_err());
+
+        let empty = HostIdentityInfo::Fixed { value: String::new() };
+        assert!(resolve_host_identity(&empty).is_err());
+
+        let missing = HostIdentityInfo::Certificate { path: "resources/missing.pem".to_string() };
+        assert!(resolve_host_identity(&missing).is_err());
+    }
+}
@@ -1,3 +1,5 @@
 pub mod sign_provider;
 pub mod hash_provider;
+pub mod identity_provider;
+pub mod payload_provider;
 pub mod stream_provider;
@@ -0,0 +1,115 @@
+use std::collections::BTreeMap;
+use coset::{cbor::value::Value, iana, Algorithm, CborSerializable, CoseSign1, Header, Label, TaggedCborSerializable};
+use super::{Payload, PayloadExtractor, PayloadSignature};
+
+/// Extracts COSE_Sign1 envelopes (RFC 9052), tagged or untagged. Envelopes with a detached
+/// payload are not recognised, as there is no content to annotate
+pub struct CoseSign1Extractor;
+
+impl PayloadExtractor for CoseSign1Extractor {
+    fn extract(&self, data: &[u8]) -> Option<Payload> {
+        let sign1 = CoseSign1::from_tagged_slice(data)
+            .or_else(|_| CoseSign1::from_slice(data))
+            .ok()?;
+
+        // The algorithm and key are only taken from the protected header, as the unprotected one
+        // is not covered by the signature
+        let key_id = &sign1.protected.header.key_id;
+        let key_id = match key_id.is_empty() {
+            true => None,
+            false => Some(String::from_utf8(key_id.clone()).unwrap_or_else(|_| hex::encode(key_id))),
+        };
+        let signature = sign1.protected.header.alg.as_ref()
+            .map(|alg| PayloadSignature {
+                algorithm: algorithm_name(alg),
+                key_id,
+                signing_input: sign1.tbs_data(&[]),
+                signature: sign1.signature.clone(),
+            });
+
+        let headers = header_values(&sign1.protected.header);
+
+        Some(Payload {
+            content: sign1.payload?,
+            headers,
+            signature,
+        })
+    }
+}
+
+// Header parameters beyond the registered ones, with text or integer values
+fn header_values(header: &Header) -> BTreeMap<String, String> {
+    header.rest.iter()
+        .filter_map(|(label, value)| {
+            let label = match label {
+                Label::Text(label) => label.clone(),
+                Label::Int(label) => label.to_string(),
+            };
+            match value {
+                Value::Text(value) => Some((label, value.clone())),
+                Value::Integer(value) => Some((label, i128::from(*value).to_string())),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+fn algorithm_name(alg: &Algorithm) -> String {
+    match alg {
+        Algorithm::Assigned(iana::Algorithm::EdDSA) => "EdDSA".to_string(),
+        Algorithm::Assigned(iana::Algorithm::ES256) => "ES256".to_string(),
+        Algorithm::Assigned(alg) => format!("{:?}", alg),
+        Algorithm::PrivateUse(alg) => alg.to_string(),
+        Algorithm::Text(alg) => alg.clone(),
+    }
+}
+
+
+#[cfg(test)]
+mod cose_tests {
+    use coset::{iana, CoseSign1Builder, HeaderBuilder, TaggedCborSerializable};
+    use super::{CoseSign1Extractor, PayloadExtractor};
+
+    #[test]
+    fn cose_sign1() {
+        let protected = HeaderBuilder::new()
+            .algorithm(iana::Algorithm::EdDSA)
+            .key_id(b"device-7".to_vec())
+            .build();
+        let sign1 = CoseSign1Builder::new()
+            .protected(protected)
+            .payload(b"reading".to_vec())
+            .create_signature(&[], |_| vec![4u8; 64])
+            .build();
+        let tbs = sign1.tbs_data(&[]);
+        let bytes = sign1.to_tagged_vec().unwrap();
+
+        let extracted = CoseSign1Extractor.extract(&bytes).unwrap();
+        assert_eq!(extracted.content, b"reading");
+
+        let signature = extracted.signature.unwrap();
+        assert_eq!(signature.algorithm, "EdDSA");
+        assert_eq!(signature.key_id.as_deref(), Some("device-7"));
+        assert_eq!(signature.signing_input, tbs);
+        assert_eq!(signature.signature, [4u8; 64]);
+
+        assert!(CoseSign1Extractor.extract(b"Some random data").is_none());
+    }
+
+    #[test]
+    fn unprotected_alg_and_kid_ignored() {
+        let unprotected = HeaderBuilder::new()
+            .algorithm(iana::Algorithm::EdDSA)
+            .key_id(b"device-7".to_vec())
+            .build();
+        let sign1 = CoseSign1Builder::new()
+            .unprotected(unprotected)
+            .payload(b"reading".to_vec())
+            .create_signature(&[], |_| vec![4u8; 64])
+            .build();
+
+        let extracted = CoseSign1Extractor.extract(&sign1.to_tagged_vec().unwrap()).unwrap();
+        assert_eq!(extracted.content, b"reading");
+        assert!(extracted.signature.is_none());
+    }
+}
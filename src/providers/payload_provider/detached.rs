@@ -0,0 +1,95 @@
+use std::collections::BTreeMap;
+use super::{Payload, PayloadExtractor, PayloadSignature};
+
+const SIGNATURE_HEADER: &str = "signature";
+const ALGORITHM_HEADER: &str = "signature-algorithm";
+const KEY_ID_HEADER: &str = "key-id";
+
+/// Extracts a body preceded by signature headers, separated from it by an empty line:
+///
+/// ```text
+/// Signature: <base64 signature over the body>
+/// Signature-Algorithm: EdDSA
+/// Key-Id: sensor-1
+///
+/// <body>
+/// ```
+///
+/// `Signature-Algorithm` defaults to EdDSA and `Key-Id` is optional. Header names are case
+/// insensitive
+pub struct DetachedSignatureExtractor;
+
+impl PayloadExtractor for DetachedSignatureExtractor {
+    fn extract(&self, data: &[u8]) -> Option<Payload> {
+        let (headers, body) = split_headers(data)?;
+        let headers = std::str::from_utf8(headers).ok()?;
+
+        let mut signature = None;
+        let mut algorithm = "EdDSA".to_string();
+        let mut key_id = None;
+        for line in headers.lines() {
+            let (name, value) = line.split_once(':')?;
+            let (name, value) = (name.trim().to_lowercase(), value.trim());
+            match name.as_str() {
+                SIGNATURE_HEADER => signature = Some(base64::decode(value).ok()?),
+                ALGORITHM_HEADER => algorithm = value.to_string(),
+                KEY_ID_HEADER => key_id = Some(value.to_string()),
+                _ => {}
+            }
+        }
+
+        Some(Payload {
+            content: body.to_vec(),
+            // None of the headers are covered by the signature
+            headers: BTreeMap::new(),
+            signature: Some(PayloadSignature {
+                algorithm,
+                key_id,
+                signing_input: body.to_vec(),
+                signature: signature?,
+            }),
+        })
+    }
+}
+
+// Splits at the first empty line, accepting either LF or CRLF line endings
+fn split_headers(data: &[u8]) -> Option<(&[u8], &[u8])> {
+    [&b"\r\n\r\n"[..], &b"\n\n"[..]].iter()
+        .filter_map(|separator| {
+            data.windows(separator.len())
+                .position(|window| window == *separator)
+                .map(|idx| (idx, separator.len()))
+        })
+        .min_by_key(|(idx, _)| *idx)
+        .map(|(idx, len)| (&data[..idx], &data[idx + len..]))
+}
+
+
+#[cfg(test)]
+mod detached_tests {
+    use super::{DetachedSignatureExtractor, PayloadExtractor};
+
+    #[test]
+    fn detached_signature() {
+        let data = format!(
+            "Signature: {}\r\nSignature-Algorithm: ES256\r\nkey-id: sensor-1\r\n\r\nthe body\n\nwith lines",
+            base64::encode([5u8; 64])
+        );
+        let extracted = DetachedSignatureExtractor.extract(data.as_bytes()).unwrap();
+        assert_eq!(extracted.content, b"the body\n\nwith lines");
+        assert!(extracted.headers.is_empty());
+
+        let signature = extracted.signature.unwrap();
+        assert_eq!(signature.algorithm, "ES256");
+        assert_eq!(signature.key_id.as_deref(), Some("sensor-1"));
+        assert_eq!(signature.signing_input, b"the body\n\nwith lines");
+        assert_eq!(signature.signature, [5u8; 64]);
+    }
+
+    #[test]
+    fn missing_signature_header() {
+        assert!(DetachedSignatureExtractor.extract(b"Key-Id: sensor-1\n\nthe body").is_none());
+        assert!(DetachedSignatureExtractor.extract(b"Some random data").is_none());
+        assert!(DetachedSignatureExtractor.extract(b"Some random\n\ndata").is_none());
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::BTreeMap;
+use serde_json::{Map, Value};
+use super::{Payload, PayloadExtractor, PayloadSignature};
+
+/// Extracts JSON Web Signatures (RFC 7515) in either the compact or the JSON serialisation. Only
+/// the first signature of a general JSON serialisation is used
+pub struct JwsExtractor;
+
+impl PayloadExtractor for JwsExtractor {
+    fn extract(&self, data: &[u8]) -> Option<Payload> {
+        match serde_json::from_slice::<Map<String, Value>>(data) {
+            Ok(jws) => extract_json(&jws),
+            Err(_) => extract_compact(std::str::from_utf8(data).ok()?.trim()),
+        }
+    }
+}
+
+fn extract_compact(jws: &str) -> Option<Payload> {
+    let mut parts = jws.split('.');
+    let (protected, payload, signature) = (parts.next()?, parts.next()?, parts.next()?);
+    if parts.next().is_some() || payload.is_empty() {
+        return None
+    }
+
+    let header = decode_header(protected)?;
+    build_payload(protected, &header, payload, signature)
+}
+
+fn extract_json(jws: &Map<String, Value>) -> Option<Payload> {
+    let payload = jws.get("payload")?.as_str()?;
+    // Flattened serialisation carries the signature at the top level
+    let signature = match jws.get("signatures") {
+        Some(signatures) => signatures.as_array()?.first()?.as_object()?,
+        None => jws,
+    };
+
+    let protected = signature.get("protected").and_then(Value::as_str).unwrap_or_default();
+    let header = match protected.is_empty() {
+        true => Map::new(),
+        false => decode_header(protected)?,
+    };
+    // The unprotected header is not covered by the signature, so nothing is taken from it
+    build_payload(protected, &header, payload, signature.get("signature")?.as_str()?)
+}
+
+fn build_payload(
+    protected: &str,
+    header: &Map<String, Value>,
+    payload: &str,
+    signature: &str
+) -> Option<Payload> {
+    let header_value = |name: &str| {
+        header.get(name)
+            .and_then(Value::as_str)
+            .map(|value| value.to_string())
+    };
+
+    let headers = header.iter()
+        .filter_map(|(name, value)| match value {
+            Value::String(value) => Some((name.clone(), value.clone())),
+            Value::Number(value) => Some((name.clone(), value.to_string())),
+            _ => None,
+        })
+        .collect::<BTreeMap<String, String>>();
+
+    let signature = match header_value("alg") {
+        Some(algorithm) => Some(PayloadSignature {
+            algorithm,
+            key_id: header_value("kid"),
+            signing_input: format!("{}.{}", protected, payload).into_bytes(),
+            signature: decode(signature)?,
+        }),
+        None => None,
+    };
+
+    Some(Payload {
+        content: decode(payload)?,
+        headers,
+        signature,
+    })
+}
+
+fn decode_header(protected: &str) -> Option<Map<String, Value>> {
+    serde_json::from_slice(&decode(protected)?).ok()
+}
+
+fn decode(segment: &str) -> Option<Vec<u8>> {
+    base64::decode_config(segment, base64::URL_SAFE_NO_PAD).ok()
+}
+
+
+#[cfg(test)]
+mod jws_tests {
+    use super::{JwsExtractor, PayloadExtractor};
+
+    fn encode(data: &[u8]) -> String {
+        base64::encode_config(data, base64::URL_SAFE_NO_PAD)
+    }
+
+    #[test]
+    fn compact_jws() {
+        let header = encode(br#"{"alg":"EdDSA","kid":"sensor-1"}"#);
+        let payload = encode(br#"{"temperature":21.5}"#);
+        let signature = encode(&[2u8; 64]);
+        let jws = format!("{}.{}.{}", header, payload, signature);
+
+        let extracted = JwsExtractor.extract(jws.as_bytes()).unwrap();
+        assert_eq!(extracted.content, br#"{"temperature":21.5}"#);
+
+        let signature = extracted.signature.unwrap();
+        assert_eq!(signature.algorithm, "EdDSA");
+        assert_eq!(signature.key_id.as_deref(), Some("sensor-1"));
+        assert_eq!(signature.signing_input, format!("{}.{}", header, payload).into_bytes());
+        assert_eq!(signature.signature, [2u8; 64]);
+        assert_eq!(extracted.headers.get("kid").map(String::as_str), Some("sensor-1"));
+    }
+
+    #[test]
+    fn json_jws() {
+        let protected = encode(br#"{"alg":"ES256","kid":"sensor-2"}"#);
+        let payload = encode(b"reading");
+        let signature = encode(&[3u8; 64]);
+
+        let general = format!(
+            r#"{{"payload":"{}","signatures":[{{"protected":"{}","header":{{"kid":"gateway"}},"signature":"{}"}}]}}"#,
+            payload, protected, signature
+        );
+        let flattened = format!(
+            r#"{{"payload":"{}","protected":"{}","header":{{"kid":"gateway"}},"signature":"{}"}}"#,
+            payload, protected, signature
+        );
+
+        for jws in [general, flattened] {
+            let extracted = JwsExtractor.extract(jws.as_bytes()).unwrap();
+            assert_eq!(extracted.content, b"reading");
+            let signature = extracted.signature.unwrap();
+            assert_eq!(signature.algorithm, "ES256");
+            assert_eq!(signature.key_id.as_deref(), Some("sensor-2"));
+        }
+    }
+
+    #[test]
+    fn unprotected_alg_and_kid_ignored() {
+        let payload = encode(b"reading");
+        let signature = encode(&[3u8; 64]);
+
+        let protected = encode(br#"{"alg":"ES256"}"#);
+        let jws = format!(
+            r#"{{"payload":"{}","protected":"{}","header":{{"kid":"gateway"}},"signature":"{}"}}"#,
+            payload, protected, signature
+        );
+        let extracted = JwsExtractor.extract(jws.as_bytes()).unwrap();
+        assert!(extracted.headers.get("kid").is_none());
+        assert!(extracted.signature.unwrap().key_id.is_none());
+
+        let jws = format!(
+            r#"{{"payload":"{}","header":{{"alg":"EdDSA","kid":"gateway"}},"signature":"{}"}}"#,
+            payload, signature
+        );
+        let extracted = JwsExtractor.extract(jws.as_bytes()).unwrap();
+        assert_eq!(extracted.content, b"reading");
+        assert!(extracted.signature.is_none());
+    }
+
+    #[test]
+    fn not_a_jws() {
+        assert!(JwsExtractor.extract(b"Some random data").is_none());
+        assert!(JwsExtractor.extract(b"some.dotted.data").is_none());
+        assert!(JwsExtractor.extract(br#"{"seed":"data","signature":""}"#).is_none());
+    }
+}
@@ -0,0 +1,89 @@
+mod cose;
+mod detached;
+mod jws;
+mod signable;
+
+use std::collections::BTreeMap;
+
+pub use cose::CoseSign1Extractor;
+pub use detached::DetachedSignatureExtractor;
+pub use jws::JwsExtractor;
+pub use signable::SignableExtractor;
+
+/// The parts of a payload annotators act on
+#[derive(Debug, Clone, PartialEq)]
+pub struct Payload {
+    /// Bytes the annotation key is derived from
+    pub content: Vec<u8>,
+    /// Signature carried by the payload, if it has one
+    pub signature: Option<PayloadSignature>,
+    /// Integrity protected envelope header values with a string or integer representation, such
+    /// as JWS or COSE protected header parameters. Headers the signature does not cover are left
+    /// out, so annotators can rely on these
+    pub headers: BTreeMap<String, String>,
+}
+
+impl Payload {
+    /// A payload in no known format, the whole of the data is the content
+    pub fn raw(data: &[u8]) -> Self {
+        Payload {
+            content: data.to_vec(),
+            signature: None,
+            headers: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PayloadSignature {
+    /// JOSE name of the signing algorithm (e.g. EdDSA, ES256)
+    pub algorithm: String,
+    /// Identifier of the signing key, if the envelope names one
+    pub key_id: Option<String>,
+    /// The exact bytes the signature was computed over
+    pub signing_input: Vec<u8>,
+    pub signature: Vec<u8>,
+}
+
+/// Recognises one payload format and pulls out the bytes to hash and the signature to verify
+pub trait PayloadExtractor {
+    /// Returns `None` if the data is not in this extractor's format
+    fn extract(&self, data: &[u8]) -> Option<Payload>;
+}
+
+/// Tries each extractor in turn, treating data none of them recognise as raw content
+#[derive(Default)]
+pub struct PayloadExtractorChain {
+    extractors: Vec<Box<dyn PayloadExtractor + Send + Sync>>,
+}
+
+impl PayloadExtractorChain {
+    pub fn new(extractors: Vec<Box<dyn PayloadExtractor + Send + Sync>>) -> Self {
+        PayloadExtractorChain { extractors }
+    }
+
+    /// Adds an extractor that is tried before the existing ones
+    pub fn with_extractor<E: PayloadExtractor + Send + Sync + 'static>(mut self, extractor: E) -> Self {
+        self.extractors.insert(0, Box::new(extractor));
+        self
+    }
+
+    pub fn extract(&self, data: &[u8]) -> Payload {
+        self.extractors.iter()
+            .find_map(|extractor| extractor.extract(data))
+            .unwrap_or_else(|| Payload::raw(data))
+    }
+}
+
+
+#[cfg(test)]
+mod payload_tests {
+    use super::{Payload, PayloadExtractorChain, SignableExtractor, JwsExtractor};
+
+    #[test]
+    fn unrecognised_payload_is_raw() {
+        let chain = PayloadExtractorChain::new(vec![Box::new(SignableExtractor), Box::new(JwsExtractor)]);
+        let data = "Some random data".as_bytes();
+        assert_eq!(chain.extract(data), Payload::raw(data));
+    }
+}
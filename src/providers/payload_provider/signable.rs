@@ -0,0 +1,49 @@
+use std::collections::BTreeMap;
+use crate::config::Signable;
+use super::{Payload, PayloadExtractor, PayloadSignature};
+
+/// Extracts the alvarium `{seed, signature}` shape, where the signature is a hex Ed25519
+/// signature over the seed
+pub struct SignableExtractor;
+
+impl PayloadExtractor for SignableExtractor {
+    fn extract(&self, data: &[u8]) -> Option<Payload> {
+        let signable: Signable = serde_json::from_slice(data).ok()?;
+        let content = signable.seed.into_bytes();
+        let key_id = signable.kid;
+        let signature = hex::decode(&signable.signature).ok()
+            .map(|signature| PayloadSignature {
+                algorithm: "EdDSA".to_string(),
+                key_id,
+                signing_input: content.clone(),
+                signature,
+            });
+        Some(Payload { content, signature, headers: BTreeMap::new() })
+    }
+}
+
+
+#[cfg(test)]
+mod signable_tests {
+    use crate::config::Signable;
+    use super::{PayloadExtractor, SignableExtractor};
+
+    #[test]
+    fn signable_extractor_test() {
+        let signable = Signable::new("Some random data".to_string(), hex::encode([1u8; 64]));
+        let payload = SignableExtractor.extract(&signable.to_bytes()).unwrap();
+        assert_eq!(payload.content, "Some random data".as_bytes());
+
+        let signature = payload.signature.unwrap();
+        assert_eq!(signature.signing_input, payload.content);
+        assert_eq!(signature.signature, [1u8; 64]);
+
+        assert!(signature.key_id.is_none());
+
+        let signable = signable.with_key_id("sensor-1");
+        let payload = SignableExtractor.extract(&signable.to_bytes()).unwrap();
+        assert_eq!(payload.signature.unwrap().key_id.as_deref(), Some("sensor-1"));
+
+        assert!(SignableExtractor.extract("Some random data".as_bytes()).is_none());
+    }
+}
@@ -65,7 +65,7 @@ pub(crate) fn get_pub_key(key: &str) -> Result<PublicKey> {
 }
 
 
-fn get_signature(signature: &[u8]) -> Result<Signature> {
+pub(crate) fn get_signature(signature: &[u8]) -> Result<Signature> {
     match <[u8;SIGNATURE_LENGTH]>::try_from(signature) {
         Ok(resized) => Ok(Signature::from_bytes(resized)),
         Err(_) => Err(Error::IncorrectKeySize(signature.len(), SIGNATURE_LENGTH))
@@ -0,0 +1,260 @@
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+use log::warn;
+use p256::ecdsa::signature::Verifier;
+use crate::errors::{Error, Result};
+use super::{get_pub_key, get_signature, SignatureProviderWrap};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KeyType {
+    #[serde(rename = "ed25519")]
+    Ed25519,
+    #[serde(rename = "p256")]
+    P256,
+}
+
+/// A keyring entry as stored on disk. Ed25519 keys are the hex encoded 32 byte key, P-256 keys
+/// the hex encoded SEC1 point (compressed or uncompressed)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KeyEntry {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub key_type: KeyType,
+    pub public: String,
+}
+
+pub enum VerifyingKey {
+    Ed25519(crypto::signatures::ed25519::PublicKey),
+    P256(p256::ecdsa::VerifyingKey),
+}
+
+impl VerifyingKey {
+    /// Builds a key from a hex encoded public key, telling the type apart by its length
+    pub fn from_hex(key: &str) -> Result<Self> {
+        let key_type = match hex::decode(key)?.len() {
+            crypto::signatures::ed25519::PUBLIC_KEY_LENGTH => KeyType::Ed25519,
+            _ => KeyType::P256,
+        };
+        VerifyingKey::new(&KeyEntry { id: String::new(), key_type, public: key.to_string() })
+    }
+
+    pub fn new(entry: &KeyEntry) -> Result<Self> {
+        match entry.key_type {
+            KeyType::Ed25519 => Ok(VerifyingKey::Ed25519(get_pub_key(&entry.public)?)),
+            KeyType::P256 => {
+                let bytes = hex::decode(&entry.public)?;
+                p256::ecdsa::VerifyingKey::from_sec1_bytes(&bytes)
+                    .map(VerifyingKey::P256)
+                    .map_err(|_| Error::PublicKeyFailure)
+            }
+        }
+    }
+
+    /// The JOSE name of the algorithm the key verifies
+    pub fn algorithm(&self) -> &'static str {
+        match self {
+            VerifyingKey::Ed25519(_) => "EdDSA",
+            VerifyingKey::P256(_) => "ES256",
+        }
+    }
+
+    /// Verifies `signature` over `content` with the JOSE named `algorithm`. A key is only used
+    /// for its own algorithm, any other pairing does not verify
+    pub fn verify(&self, algorithm: &str, content: &[u8], signature: &[u8]) -> Result<bool> {
+        match (self, algorithm) {
+            (VerifyingKey::Ed25519(key), "EdDSA") => {
+                // A signature of the wrong length does not verify
+                Ok(get_signature(signature).map_or(false, |signature| key.verify(&signature, content)))
+            }
+            (VerifyingKey::P256(key), "ES256") => {
+                // JOSE and COSE carry the fixed size r || s form, other envelopes may use DER
+                let signature = p256::ecdsa::Signature::from_slice(signature)
+                    .or_else(|_| p256::ecdsa::Signature::from_der(signature));
+                Ok(signature.map_or(false, |signature| key.verify(content, &signature).is_ok()))
+            }
+            _ => Ok(false),
+        }
+    }
+}
+
+// Extensions of the public key files loaded from a keyring directory
+const KEY_FILE_EXTENSIONS: [&str; 2] = ["key", "pub"];
+
+/// Public keys of trusted producers, indexed by key ID
+#[derive(Default)]
+pub struct Keyring {
+    keys: HashMap<String, VerifyingKey>,
+}
+
+impl Keyring {
+    /// Loads either a JSON file holding an array of key entries, or a directory of hex encoded
+    /// public key files where each file name (less its `.key` or `.pub` extension) is the key ID.
+    /// Other files in the directory are ignored, and key files that do not parse are skipped
+    pub fn load(path: &str) -> Result<Self> {
+        if std::path::Path::new(path).is_dir() {
+            return Keyring::load_dir(path)
+        }
+
+        let file = std::fs::read(path).map_err(Error::KeyringError)?;
+        let entries: Vec<KeyEntry> = serde_json::from_slice(&file)?;
+        Keyring::from_entries(&entries)
+    }
+
+    fn load_dir(path: &str) -> Result<Self> {
+        let mut keyring = Keyring::default();
+        for entry in std::fs::read_dir(path).map_err(Error::KeyringError)? {
+            let path = entry.map_err(Error::KeyringError)?.path();
+            let is_key_file = path.extension()
+                .and_then(|extension| extension.to_str())
+                .map_or(false, |extension| KEY_FILE_EXTENSIONS.contains(&extension));
+            let key_id = match path.file_stem().and_then(|stem| stem.to_str()) {
+                Some(key_id) if is_key_file && path.is_file() && !key_id.starts_with('.') => key_id.to_string(),
+                _ => continue,
+            };
+            let key = std::fs::read_to_string(&path).map_err(Error::KeyringError)?;
+            match VerifyingKey::from_hex(key.trim()) {
+                Ok(key) => keyring.insert(&key_id, key),
+                Err(e) => warn!("Skipping keyring file {}: {}", path.display(), e),
+            }
+        }
+        Ok(keyring)
+    }
+
+    pub fn from_entries(entries: &[KeyEntry]) -> Result<Self> {
+        let mut keyring = Keyring::default();
+        for entry in entries {
+            keyring.insert(&entry.id, VerifyingKey::new(entry)?);
+        }
+        Ok(keyring)
+    }
+
+    pub fn insert(&mut self, id: &str, key: VerifyingKey) {
+        self.keys.insert(id.to_string(), key);
+    }
+
+    pub fn get(&self, id: &str) -> Option<&VerifyingKey> {
+        self.keys.get(id)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Verifies against the trusted key named by `key_id`. Signatures without a key ID are checked
+    /// against the SDK's own key, a key ID the keyring does not hold never verifies
+    pub fn verify(
+        &self,
+        key_id: Option<&str>,
+        provider: &SignatureProviderWrap,
+        algorithm: &str,
+        content: &[u8],
+        signature: &[u8]
+    ) -> Result<bool> {
+        if let Some(key_id) = key_id {
+            return match self.get(key_id) {
+                Some(key) => key.verify(algorithm, content, signature),
+                None => Ok(false),
+            }
+        }
+
+        match (provider, algorithm) {
+            (SignatureProviderWrap::Ed25519(provider), "EdDSA") if get_signature(signature).is_ok() => {
+                Ok(alvarium_annotator::SignProvider::verify(provider, content, signature)?)
+            },
+            _ => Ok(false),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod keyring_tests {
+    use alvarium_annotator::SignProvider;
+    use crypto::signatures::ed25519::SecretKey;
+    use p256::ecdsa::{signature::Signer, SigningKey};
+    use crate::config;
+    use crate::factories::new_signature_provider;
+    use super::{KeyEntry, KeyType, Keyring, VerifyingKey};
+
+    #[test]
+    fn verify_with_keyring() {
+        let signing_key = SigningKey::from_slice(&[7u8; 32]).unwrap();
+        let public = hex::encode(signing_key.verifying_key().to_encoded_point(true).as_bytes());
+        let keyring = Keyring::from_entries(&[KeyEntry {
+            id: "gateway".to_string(),
+            key_type: KeyType::P256,
+            public,
+        }]).unwrap();
+
+        let signature: p256::ecdsa::Signature = signing_key.sign(b"reading");
+        let key = keyring.get("gateway").unwrap();
+        assert!(key.verify("ES256", b"reading", &signature.to_bytes()).unwrap());
+        assert!(key.verify("ES256", b"reading", signature.to_der().as_bytes()).unwrap());
+        assert!(!key.verify("ES256", b"other reading", &signature.to_bytes()).unwrap());
+        assert!(!key.verify("EdDSA", b"reading", &signature.to_bytes()).unwrap());
+        assert!(keyring.get("unknown").is_none());
+    }
+
+    #[test]
+    fn verify_by_key_id() {
+        let config: config::SdkInfo = serde_json::from_slice(crate::CONFIG_BYTES.as_slice()).unwrap();
+        let sdk_key = new_signature_provider(&config.signature).unwrap();
+
+        let producer_key = SecretKey::generate().unwrap();
+        let mut keyring = Keyring::default();
+        keyring.insert("producer", VerifyingKey::from_hex(&hex::encode(producer_key.public_key().to_bytes())).unwrap());
+
+        let data = b"A data packet to sign";
+        let producer_sig = producer_key.sign(data).to_bytes();
+        assert!(keyring.verify(Some("producer"), &sdk_key, "EdDSA", data, &producer_sig).unwrap());
+        assert!(!keyring.verify(None, &sdk_key, "EdDSA", data, &producer_sig).unwrap());
+
+        // Without a key ID the SDK's own key is used, an unknown key ID never falls back to it
+        let sdk_sig = hex::decode(sdk_key.sign(data).unwrap()).unwrap();
+        assert!(keyring.verify(None, &sdk_key, "EdDSA", data, &sdk_sig).unwrap());
+        assert!(!keyring.verify(Some("someone else"), &sdk_key, "EdDSA", data, &sdk_sig).unwrap());
+    }
+
+    #[test]
+    fn load_keyring_dir() {
+        let dir = std::env::temp_dir().join("alvarium_keyring_dir");
+        std::fs::create_dir_all(&dir).unwrap();
+        let signing_key = SigningKey::from_slice(&[7u8; 32]).unwrap();
+        let public = hex::encode(signing_key.verifying_key().to_encoded_point(false).as_bytes());
+        std::fs::write(dir.join("gateway.pub"), format!("{}\n", public)).unwrap();
+        std::fs::write(
+            dir.join("sensor-1.pub"),
+            std::fs::read_to_string("resources/test_keys/public.key").unwrap()
+        ).unwrap();
+
+        let keyring = Keyring::load(dir.to_str().unwrap()).unwrap();
+        assert!(matches!(keyring.get("gateway"), Some(VerifyingKey::P256(_))));
+        assert!(matches!(keyring.get("sensor-1"), Some(VerifyingKey::Ed25519(_))));
+    }
+
+    #[test]
+    fn keyring_dir_ignores_stray_files() {
+        let dir = std::env::temp_dir().join(format!("alvarium_keyring_{}", ulid::Ulid::new()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("README.md"), "Trusted producer keys\n").unwrap();
+        std::fs::copy("resources/test_certs/ca.pem", dir.join("ca.pem")).unwrap();
+        std::fs::write(dir.join("broken.pub"), "not a key").unwrap();
+        std::fs::write(
+            dir.join("sensor-1.key"),
+            std::fs::read_to_string("resources/test_keys/public.key").unwrap()
+        ).unwrap();
+
+        let keyring = Keyring::load(dir.to_str().unwrap()).unwrap();
+        assert!(matches!(keyring.get("sensor-1"), Some(VerifyingKey::Ed25519(_))));
+        for key_id in ["README", "ca", "broken"] {
+            assert!(keyring.get(key_id).is_none(), "{}", key_id);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn invalid_keyring_entry() {
+        let entry = KeyEntry { id: "bad".to_string(), key_type: KeyType::P256, public: hex::encode([1u8; 33]) };
+        assert!(Keyring::from_entries(&[entry]).is_err());
+    }
+}
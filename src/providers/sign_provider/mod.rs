@@ -1,7 +1,9 @@
 mod ed25519;
+mod keyring;
 
 use crate::errors::Result;
 pub use ed25519::*;
+pub use keyring::*;
 
 pub enum SignatureProviderWrap {
     Ed25519(Ed25519Provider)
@@ -0,0 +1,528 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use crate::config::{CoapStreamConfig, StreamConfig, StreamInfo};
+use alvarium_annotator::{MessageWrapper, Publisher};
+use tokio::net::UdpSocket;
+use webrtc_dtls::cipher_suite::CipherSuiteId;
+use webrtc_dtls::conn::DTLSConn;
+use webrtc_util::Conn;
+use log::{debug, warn};
+use crate::errors::{Error, Result};
+
+// Message types, codes and option numbers from RFC 7252
+const CONFIRMABLE: u8 = 0;
+const NON_CONFIRMABLE: u8 = 1;
+const ACKNOWLEDGEMENT: u8 = 2;
+const RESET: u8 = 3;
+const EMPTY: u8 = 0x00;
+const POST: u8 = 0x02;
+const URI_PATH: u16 = 11;
+const CONTENT_FORMAT: u16 = 12;
+const APPLICATION_CBOR: u8 = 60;
+const PAYLOAD_MARKER: u8 = 0xFF;
+// Block-wise transfer of request payloads, from RFC 7959
+const BLOCK1: u16 = 27;
+const MAX_BLOCK_SZX: u8 = 6;
+
+const MAX_DATAGRAM: usize = 1152;
+
+/// A CoAP message, just as much of it as publishing needs
+#[derive(Debug, Clone, PartialEq)]
+struct CoapMessage {
+    kind: u8,
+    code: u8,
+    message_id: u16,
+    token: Vec<u8>,
+    /// Option numbers and values, in ascending number order
+    options: Vec<(u16, Vec<u8>)>,
+    payload: Vec<u8>,
+}
+
+impl CoapMessage {
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = vec![0x40 | (self.kind << 4) | self.token.len() as u8, self.code];
+        bytes.extend(self.message_id.to_be_bytes());
+        bytes.extend(&self.token);
+
+        let mut previous = 0;
+        for (number, value) in &self.options {
+            let (delta, delta_ext) = option_nibble(number - previous);
+            let (length, length_ext) = option_nibble(value.len() as u16);
+            bytes.push(delta << 4 | length);
+            bytes.extend(delta_ext);
+            bytes.extend(length_ext);
+            bytes.extend(value);
+            previous = *number;
+        }
+
+        if !self.payload.is_empty() {
+            bytes.push(PAYLOAD_MARKER);
+            bytes.extend(&self.payload);
+        }
+        bytes
+    }
+
+    fn decode(bytes: &[u8]) -> Option<CoapMessage> {
+        if bytes.len() < 4 || bytes[0] >> 6 != 1 {
+            return None
+        }
+        let token_len = (bytes[0] & 0x0F) as usize;
+        let mut message = CoapMessage {
+            kind: (bytes[0] >> 4) & 0x03,
+            code: bytes[1],
+            message_id: u16::from_be_bytes([bytes[2], bytes[3]]),
+            token: bytes.get(4..4 + token_len)?.to_vec(),
+            options: Vec::new(),
+            payload: Vec::new(),
+        };
+
+        let mut rest = &bytes[4 + token_len..];
+        let mut number = 0;
+        while let Some((&first, tail)) = rest.split_first() {
+            if first == PAYLOAD_MARKER {
+                message.payload = tail.to_vec();
+                break
+            }
+            let (delta, tail) = read_option_nibble(first >> 4, tail)?;
+            let (length, tail) = read_option_nibble(first & 0x0F, tail)?;
+            number = number.checked_add(delta)?;
+            message.options.push((number, tail.get(..length as usize)?.to_vec()));
+            rest = &tail[length as usize..];
+        }
+        Some(message)
+    }
+
+    fn class(&self) -> u8 {
+        self.code >> 5
+    }
+}
+
+// Option deltas and lengths above 12 spill into one or two extension bytes
+fn option_nibble(value: u16) -> (u8, Vec<u8>) {
+    match value {
+        0..=12 => (value as u8, vec![]),
+        13..=268 => (13, vec![(value - 13) as u8]),
+        _ => (14, (value - 269).to_be_bytes().to_vec()),
+    }
+}
+
+fn read_option_nibble(nibble: u8, bytes: &[u8]) -> Option<(u16, &[u8])> {
+    match nibble {
+        0..=12 => Some((nibble as u16, bytes)),
+        13 => Some((*bytes.first()? as u16 + 13, &bytes[1..])),
+        14 => Some((u16::from_be_bytes([*bytes.first()?, *bytes.get(1)?]).checked_add(269)?, &bytes[2..])),
+        _ => None,
+    }
+}
+
+// Block number, whether more blocks follow, and the block size exponent, in as few bytes as
+// possible
+fn block_option(num: usize, more: bool, szx: u8) -> Vec<u8> {
+    let value = (num as u32) << 4 | (more as u32) << 3 | szx as u32;
+    let bytes = value.to_be_bytes();
+    let skip = bytes.iter().take_while(|byte| **byte == 0).count();
+    bytes[skip..].to_vec()
+}
+
+pub struct CoapPublisher {
+    cfg: CoapStreamConfig,
+    conn: Option<Arc<dyn Conn + Send + Sync>>,
+    message_id: u16,
+}
+
+impl CoapPublisher {
+    fn request(&mut self, payload: Vec<u8>, block: Option<Vec<u8>>) -> CoapMessage {
+        self.message_id = self.message_id.wrapping_add(1);
+        let mut options: Vec<(u16, Vec<u8>)> = self.cfg.resource.split('/')
+            .filter(|segment| !segment.is_empty())
+            .map(|segment| (URI_PATH, segment.as_bytes().to_vec()))
+            .collect();
+        options.push((CONTENT_FORMAT, vec![APPLICATION_CBOR]));
+        if let Some(block) = block {
+            options.push((BLOCK1, block));
+        }
+
+        CoapMessage {
+            kind: if self.cfg.confirmable { CONFIRMABLE } else { NON_CONFIRMABLE },
+            code: POST,
+            message_id: self.message_id,
+            token: rand::random::<[u8; 4]>().to_vec(),
+            options,
+            payload,
+        }
+    }
+
+    // Largest block size exponent whose blocks fit a datagram alongside the header and options
+    fn block_szx(&mut self) -> Result<u8> {
+        let overhead = self.request(Vec::new(), Some(vec![0xFF; 3])).encode().len() + 1;
+        (0..=MAX_BLOCK_SZX).rev()
+            .find(|szx| overhead + (16 << szx) <= MAX_DATAGRAM)
+            .ok_or_else(|| Error::CoapError(format!("resource {} leaves no room for a payload", self.cfg.resource)))
+    }
+
+    // Retransmits a confirmable request with exponential back off until it is acknowledged,
+    // then waits out a separate response if the acknowledgement did not carry one
+    async fn exchange(&self, conn: &Arc<dyn Conn + Send + Sync>, request: &CoapMessage) -> Result<()> {
+        let bytes = request.encode();
+        let mut timeout = Duration::from_millis(self.cfg.ack_timeout_ms);
+        let mut buf = vec![0u8; MAX_DATAGRAM];
+        let mut acknowledged = false;
+        let mut attempt = 0;
+
+        conn.send(&bytes).await.map_err(|e| Error::CoapError(e.to_string()))?;
+        loop {
+            let received = match tokio::time::timeout(timeout, conn.recv(&mut buf)).await {
+                Ok(received) => received.map_err(|e| Error::CoapError(e.to_string()))?,
+                Err(_) if attempt < self.cfg.max_retransmit => {
+                    attempt += 1;
+                    warn!("No CoAP response to message {}, attempt {}", request.message_id, attempt);
+                    if !acknowledged {
+                        conn.send(&bytes).await.map_err(|e| Error::CoapError(e.to_string()))?;
+                    }
+                    timeout *= 2;
+                    continue
+                }
+                Err(_) => return Err(Error::CoapError(format!("no response to message {}", request.message_id))),
+            };
+            let response = match CoapMessage::decode(&buf[..received]) {
+                Some(response) => response,
+                None => continue,
+            };
+
+            if response.kind == RESET && response.message_id == request.message_id {
+                return Err(Error::CoapError("request was reset by the server".to_string()))
+            }
+            if response.kind == ACKNOWLEDGEMENT && response.message_id == request.message_id && response.code == EMPTY {
+                debug!("CoAP message {} acknowledged, awaiting separate response", request.message_id);
+                acknowledged = true;
+                continue
+            }
+            if response.token != request.token {
+                continue
+            }
+            if response.kind == CONFIRMABLE {
+                let ack = CoapMessage {
+                    kind: ACKNOWLEDGEMENT,
+                    code: EMPTY,
+                    message_id: response.message_id,
+                    token: Vec::new(),
+                    options: Vec::new(),
+                    payload: Vec::new(),
+                };
+                conn.send(&ack.encode()).await.map_err(|e| Error::CoapError(e.to_string()))?;
+            }
+            return match response.class() {
+                2 => Ok(()),
+                class => Err(Error::CoapError(format!("server responded {}.{:02}", class, response.code & 0x1F))),
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Publisher for CoapPublisher {
+    type StreamConfig = StreamInfo;
+    type Error = crate::errors::Error;
+    async fn new(cfg: &StreamInfo) -> Result<Self> {
+        match &cfg.config {
+            StreamConfig::Coap(cfg) => {
+                if cfg.is_secure() && cfg.dtls.is_none() {
+                    return Err(Error::IncorrectConfig)
+                }
+                Ok(CoapPublisher {
+                    cfg: cfg.clone(),
+                    conn: None,
+                    message_id: rand::random(),
+                })
+            }
+            _ => Err(Error::IncorrectConfig)
+        }
+    }
+
+    async fn close(&mut self) -> Result<()> {
+        if let Some(conn) = self.conn.take() {
+            conn.close().await.map_err(|e| Error::CoapError(e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    async fn connect(&mut self) -> Result<()> {
+        let server = tokio::net::lookup_host((self.cfg.provider.host.as_str(), self.cfg.provider.port as u16))
+            .await
+            .map_err(|e| Error::CoapError(e.to_string()))?
+            .next()
+            .ok_or(Error::IncorrectConfig)?;
+        let local: SocketAddr = match server {
+            SocketAddr::V4(_) => "0.0.0.0:0".parse().unwrap(),
+            SocketAddr::V6(_) => "[::]:0".parse().unwrap(),
+        };
+        let socket = UdpSocket::bind(local).await.map_err(|e| Error::CoapError(e.to_string()))?;
+        socket.connect(server).await.map_err(|e| Error::CoapError(e.to_string()))?;
+
+        let conn: Arc<dyn Conn + Send + Sync> = match &self.cfg.dtls {
+            Some(dtls) if self.cfg.is_secure() => {
+                let key = hex::decode(dtls.key())?;
+                let config = webrtc_dtls::config::Config {
+                    psk: Some(Arc::new(move |_hint: &[u8]| Ok::<_, webrtc_dtls::Error>(key.clone()))),
+                    psk_identity_hint: Some(dtls.identity.as_bytes().to_vec()),
+                    cipher_suites: vec![CipherSuiteId::Tls_Psk_With_Aes_128_Ccm_8],
+                    ..Default::default()
+                };
+                let dtls = DTLSConn::new(Arc::new(socket), config, true, None)
+                    .await
+                    .map_err(|e| Error::CoapError(e.to_string()))?;
+                Arc::new(dtls)
+            }
+            _ => Arc::new(socket),
+        };
+        self.conn = Some(conn);
+        Ok(())
+    }
+
+    async fn reconnect(&mut self) -> Result<()> {
+        match self.conn {
+            Some(_) => Ok(()),
+            None => self.connect().await,
+        }
+    }
+
+    async fn publish(&mut self, msg: MessageWrapper<'_>) -> Result<()> {
+        self.reconnect().await?;
+        let mut payload = Vec::new();
+        ciborium::ser::into_writer(&msg, &mut payload).map_err(|e| Error::CoapError(e.to_string()))?;
+
+        let conn = self.conn.clone().ok_or(Error::IncorrectConfig)?;
+        debug!("Posting {} bytes to coap resource {}", payload.len(), self.cfg.resource);
+        let request = self.request(payload, None);
+        if request.encode().len() <= MAX_DATAGRAM {
+            if request.kind == NON_CONFIRMABLE {
+                conn.send(&request.encode()).await.map_err(|e| Error::CoapError(e.to_string()))?;
+                return Ok(())
+            }
+            return self.exchange(&conn, &request).await
+        }
+
+        // Blocks are only sent in order if each one is acknowledged before the next
+        if !self.cfg.confirmable {
+            return Err(Error::CoapError(format!("{} byte payload does not fit a non-confirmable message", request.payload.len())))
+        }
+        let szx = self.block_szx()?;
+        let blocks: Vec<&[u8]> = request.payload.chunks(16 << szx).collect();
+        for (num, block) in blocks.iter().enumerate() {
+            let more = num + 1 < blocks.len();
+            let request = self.request(block.to_vec(), Some(block_option(num, more, szx)));
+            debug!("Posting block {} of {} to coap resource {}", num + 1, blocks.len(), self.cfg.resource);
+            self.exchange(&conn, &request).await?;
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod coap_tests {
+    use std::net::SocketAddr;
+    use alvarium_annotator::{AnnotationList, MessageWrapper, Publisher};
+    use tokio::net::UdpSocket;
+    use crate::config::{StreamConfig, StreamInfo};
+    use crate::providers::stream_provider::CoapPublisher;
+    use std::sync::Arc;
+    use webrtc_dtls::cipher_suite::CipherSuiteId;
+    use webrtc_util::conn::Listener;
+    use super::{CoapMessage, ACKNOWLEDGEMENT, BLOCK1, CONFIRMABLE, CONTENT_FORMAT, EMPTY, MAX_DATAGRAM, POST, URI_PATH};
+
+    const CHANGED: u8 = 0x44;
+    const CONTINUE: u8 = 0x5F;
+
+    fn coap_stream_info(port: u16) -> StreamInfo {
+        let mut coap_stream_info: StreamInfo = serde_json::from_slice(crate::COAP_TEST_CONFIG_BYTES.as_slice()).unwrap();
+        if let StreamConfig::Coap(cfg) = &mut coap_stream_info.config {
+            cfg.provider.host = "127.0.0.1".to_string();
+            cfg.provider.port = port as usize;
+        }
+        coap_stream_info
+    }
+
+    fn message(content: &str) -> MessageWrapper<'_> {
+        MessageWrapper {
+            action: crate::annotations::constants::ACTION_CREATE.clone(),
+            message_type: std::any::type_name::<AnnotationList>(),
+            content,
+        }
+    }
+
+    fn piggybacked(request: &CoapMessage, code: u8) -> CoapMessage {
+        CoapMessage {
+            kind: ACKNOWLEDGEMENT,
+            code,
+            message_id: request.message_id,
+            token: request.token.clone(),
+            options: vec![],
+            payload: vec![],
+        }
+    }
+
+    #[test]
+    fn coap_message_round_trip() {
+        let message = CoapMessage {
+            kind: CONFIRMABLE,
+            code: POST,
+            message_id: 0x1234,
+            token: vec![1, 2, 3, 4],
+            options: vec![
+                (URI_PATH, b"alvarium".to_vec()),
+                (URI_PATH, vec![b'a'; 20]),
+                (CONTENT_FORMAT, vec![60]),
+                (300, vec![]),
+            ],
+            payload: b"payload".to_vec(),
+        };
+        let bytes = message.encode();
+        assert_eq!(&bytes[..4], &[0x44, POST, 0x12, 0x34]);
+        assert_eq!(CoapMessage::decode(&bytes), Some(message));
+        // A two byte option delta past the largest option number
+        assert_eq!(CoapMessage::decode(&[0x40, POST, 0x12, 0x34, 0xE0, 0xFF, 0xFF]), None);
+    }
+
+    // Stands in for a CoAP server: acknowledges the first request empty and sends the response
+    // separately, then answers the second with a piggybacked response
+    async fn serve(socket: UdpSocket) -> Vec<CoapMessage> {
+        let mut buf = vec![0u8; 2048];
+        let mut requests = Vec::new();
+        for piggybacked in [false, true] {
+            let (len, peer): (usize, SocketAddr) = socket.recv_from(&mut buf).await.unwrap();
+            let request = CoapMessage::decode(&buf[..len]).unwrap();
+            let mut response = CoapMessage {
+                kind: ACKNOWLEDGEMENT,
+                code: CHANGED,
+                message_id: request.message_id,
+                token: request.token.clone(),
+                options: vec![],
+                payload: vec![],
+            };
+            if !piggybacked {
+                let ack = CoapMessage { code: EMPTY, token: vec![], ..response.clone() };
+                socket.send_to(&ack.encode(), peer).await.unwrap();
+                response.kind = CONFIRMABLE;
+                response.message_id = request.message_id.wrapping_add(100);
+                socket.send_to(&response.encode(), peer).await.unwrap();
+                // The publisher acknowledges the separate response
+                let (len, _) = socket.recv_from(&mut buf).await.unwrap();
+                assert_eq!(CoapMessage::decode(&buf[..len]).unwrap().kind, ACKNOWLEDGEMENT);
+            } else {
+                socket.send_to(&response.encode(), peer).await.unwrap();
+            }
+            requests.push(request);
+        }
+        requests
+    }
+
+    #[tokio::test]
+    async fn coap_provider_publish() {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let mut coap_stream_info: StreamInfo = serde_json::from_slice(crate::COAP_TEST_CONFIG_BYTES.as_slice()).unwrap();
+        if let StreamConfig::Coap(cfg) = &mut coap_stream_info.config {
+            cfg.provider.protocol = "coap".to_string();
+            cfg.provider.port = socket.local_addr().unwrap().port() as usize;
+        }
+        let server = tokio::spawn(serve(socket));
+
+        let mut publisher = CoapPublisher::new(&coap_stream_info).await.unwrap();
+        publisher.connect().await.unwrap();
+        let content = base64::encode(r#"{"items":[{"key":"some key"}]}"#);
+        for _ in 0..2 {
+            let data = MessageWrapper {
+                action: crate::annotations::constants::ACTION_CREATE.clone(),
+                message_type: std::any::type_name::<AnnotationList>(),
+                content: &content,
+            };
+            publisher.publish(data).await.unwrap();
+        }
+
+        let requests = server.await.unwrap();
+        let path: Vec<&[u8]> = requests[0].options.iter()
+            .filter(|(number, _)| *number == URI_PATH)
+            .map(|(_, value)| value.as_slice())
+            .collect();
+        assert_eq!(path, vec![b"alvarium".as_slice(), b"annotations".as_slice()]);
+
+        let payload: serde_json::Value = ciborium::de::from_reader(requests[1].payload.as_slice()).unwrap();
+        assert_eq!(payload["content"], content);
+        publisher.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn coap_provider_publish_block_wise() {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let mut coap_stream_info = coap_stream_info(socket.local_addr().unwrap().port());
+        if let StreamConfig::Coap(cfg) = &mut coap_stream_info.config {
+            cfg.provider.protocol = "coap".to_string();
+        }
+        // Acknowledges each block until the one without the more flag
+        let server = tokio::spawn(async move {
+            let mut buf = vec![0u8; 2048];
+            let mut blocks = Vec::new();
+            loop {
+                let (len, peer) = socket.recv_from(&mut buf).await.unwrap();
+                assert!(len <= MAX_DATAGRAM);
+                let request = CoapMessage::decode(&buf[..len]).unwrap();
+                let block = request.options.iter().find(|(number, _)| *number == BLOCK1).unwrap().1.clone();
+                let more = block.last().unwrap() & 0x08 != 0;
+                let code = if more { CONTINUE } else { CHANGED };
+                socket.send_to(&piggybacked(&request, code).encode(), peer).await.unwrap();
+                blocks.push(request);
+                if !more {
+                    return blocks
+                }
+            }
+        });
+
+        let mut publisher = CoapPublisher::new(&coap_stream_info).await.unwrap();
+        publisher.connect().await.unwrap();
+        let content = "a".repeat(3000);
+        publisher.publish(message(&content)).await.unwrap();
+
+        let blocks = server.await.unwrap();
+        assert_eq!(blocks.len(), 3);
+        let payload: Vec<u8> = blocks.iter().flat_map(|block| block.payload.clone()).collect();
+        let payload: serde_json::Value = ciborium::de::from_reader(payload.as_slice()).unwrap();
+        assert_eq!(payload["content"], content);
+
+        // Without acknowledgements there is no block-wise transfer
+        if let StreamConfig::Coap(cfg) = &mut coap_stream_info.config {
+            cfg.confirmable = false;
+        }
+        let mut publisher = CoapPublisher::new(&coap_stream_info).await.unwrap();
+        assert!(publisher.publish(message(&content)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn coaps_provider_publish() {
+        let config = webrtc_dtls::config::Config {
+            psk: Some(Arc::new(|_hint: &[u8]| Ok::<_, webrtc_dtls::Error>(hex::decode("0102030405060708090a0b0c0d0e0f10").unwrap()))),
+            psk_identity_hint: Some(b"alvarium-server".to_vec()),
+            cipher_suites: vec![CipherSuiteId::Tls_Psk_With_Aes_128_Ccm_8],
+            ..Default::default()
+        };
+        let listener = webrtc_dtls::listener::listen("127.0.0.1:0", config).await.unwrap();
+        let port = listener.addr().await.unwrap().port();
+        let server = tokio::spawn(async move {
+            let (conn, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0u8; 2048];
+            let len = conn.recv(&mut buf).await.unwrap();
+            let request = CoapMessage::decode(&buf[..len]).unwrap();
+            conn.send(&piggybacked(&request, CHANGED).encode()).await.unwrap();
+            request
+        });
+
+        let mut publisher = CoapPublisher::new(&coap_stream_info(port)).await.unwrap();
+        publisher.connect().await.unwrap();
+        publisher.publish(message("content")).await.unwrap();
+
+        let request = server.await.unwrap();
+        let payload: serde_json::Value = ciborium::de::from_reader(request.payload.as_slice()).unwrap();
+        assert_eq!(payload["content"], "content");
+        publisher.close().await.unwrap();
+    }
+}
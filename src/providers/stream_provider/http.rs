@@ -0,0 +1,426 @@
+use std::time::Duration;
+use crate::config::{HttpStreamConfig, StreamConfig, StreamInfo};
+use crate::factories::new_signature_provider;
+use crate::providers::sign_provider::SignatureProviderWrap;
+use alvarium_annotator::{MessageWrapper, Publisher, SignProvider};
+use reqwest::{Certificate, Client, Identity, StatusCode};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
+use serde_json::Value;
+use log::{debug, error, warn};
+use crate::errors::{Error, Result};
+
+pub const SIGNATURE_HEADER: &str = "X-Alvarium-Signature";
+
+// Cap on the doubling retry delay
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+pub struct HttpPublisher {
+    cfg: HttpStreamConfig,
+    client: Client,
+    sign: Option<SignatureProviderWrap>,
+    batch: Vec<Value>,
+    /// A batch taken off `batch` that some endpoints have yet to accept
+    pending: Option<Delivery>,
+}
+
+struct Delivery {
+    body: Vec<u8>,
+    signature: Option<String>,
+    /// Endpoints still owed the body
+    endpoints: Vec<String>,
+    messages: usize,
+}
+
+impl HttpPublisher {
+    fn batch_size(&self) -> usize {
+        self.cfg.batch_size.max(1)
+    }
+
+    fn buffered(&self) -> usize {
+        self.batch.len() + self.pending.as_ref().map_or(0, |delivery| delivery.messages)
+    }
+
+    fn delivery(&self, items: Vec<Value>) -> Result<Delivery> {
+        let body = match items.as_slice() {
+            [item] if self.batch_size() == 1 => serde_json::to_vec(item)?,
+            _ => serde_json::to_vec(&items)?,
+        };
+        let signature = match &self.sign {
+            Some(sign) => Some(sign.sign(&body)?),
+            None => None,
+        };
+        Ok(Delivery { body, signature, endpoints: self.cfg.endpoints.clone(), messages: items.len() })
+    }
+
+    // Finishes any pending batch, then sends full batches, and a partial one if asked to. Stops
+    // at a batch held for a retryable failure, otherwise returns the first refusal, if any
+    async fn send_batches(&mut self, partial: bool) -> Result<Option<Error>> {
+        let mut refused = None;
+        loop {
+            if self.pending.is_none() {
+                let full = self.batch.len() >= self.batch_size();
+                if self.batch.is_empty() || !(full || partial) {
+                    return Ok(refused)
+                }
+                let take = self.batch.len().min(self.batch_size());
+                let items = self.batch.drain(..take).collect();
+                self.pending = Some(self.delivery(items)?);
+            }
+            if let Some(e) = self.deliver().await? {
+                refused.get_or_insert(e);
+            }
+        }
+    }
+
+    // Posts the pending batch to the endpoints that have not accepted it yet. It stays pending for
+    // the ones failing with a retryable error, so a retry does not send it twice to the others,
+    // and is dropped for the ones refusing it outright, which would only refuse it again
+    async fn deliver(&mut self) -> Result<Option<Error>> {
+        let mut delivery = match self.pending.take() {
+            Some(delivery) => delivery,
+            None => return Ok(None),
+        };
+        let mut failed = Vec::new();
+        let mut retryable = None;
+        let mut refused = None;
+        for endpoint in std::mem::take(&mut delivery.endpoints) {
+            match self.post(&endpoint, &delivery.body, delivery.signature.as_deref()).await {
+                Ok(()) => {}
+                Err(e) if is_refusal(&e) => {
+                    error!("Dropping batch of {} refused by {}: {}", delivery.messages, endpoint, e);
+                    refused.get_or_insert(e);
+                }
+                Err(e) => {
+                    warn!("Batch of {} not delivered to {}, holding it: {}", delivery.messages, endpoint, e);
+                    retryable.get_or_insert(e);
+                    failed.push(endpoint);
+                }
+            }
+        }
+        if let Some(e) = retryable {
+            delivery.endpoints = failed;
+            self.pending = Some(delivery);
+            return Err(e)
+        }
+        Ok(refused)
+    }
+
+    async fn post(&self, endpoint: &str, body: &[u8], signature: Option<&str>) -> Result<()> {
+        let mut backoff = Duration::from_millis(self.cfg.backoff_ms).min(MAX_BACKOFF);
+        let mut attempt = 0;
+        loop {
+            let mut request = self.client.post(endpoint).body(body.to_vec());
+            if let Some(signature) = signature {
+                request = request.header(SIGNATURE_HEADER, signature);
+            }
+
+            let retry = match request.send().await {
+                Ok(response) if response.status().is_success() => return Ok(()),
+                Ok(response) => {
+                    let status = response.status();
+                    if !is_retryable(status) || attempt >= self.cfg.retries {
+                        return Err(Error::HttpEndpointError(endpoint.to_string(), status.as_u16()))
+                    }
+                    format!("status {}", status)
+                }
+                Err(e) => {
+                    if e.is_builder() || attempt >= self.cfg.retries {
+                        return Err(e.into())
+                    }
+                    e.to_string()
+                }
+            };
+
+            warn!("POST to {} failed with {}, retrying in {:?}", endpoint, retry, backoff);
+            tokio::time::sleep(backoff).await;
+            backoff = backoff.saturating_mul(2).min(MAX_BACKOFF);
+            attempt += 1;
+        }
+    }
+}
+
+fn is_retryable(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+// Failures that sending the same request again would not get past
+fn is_refusal(e: &Error) -> bool {
+    match e {
+        Error::HttpEndpointError(_, status) => StatusCode::from_u16(*status).map_or(true, |status| !is_retryable(status)),
+        Error::HttpClientError(e) => e.is_builder(),
+        _ => false,
+    }
+}
+
+#[async_trait::async_trait]
+impl Publisher for HttpPublisher {
+    type StreamConfig = StreamInfo;
+    type Error = crate::errors::Error;
+    async fn new(cfg: &StreamInfo) -> Result<Self> {
+        match &cfg.config {
+            StreamConfig::Http(cfg) => {
+                let sign = match &cfg.signature {
+                    Some(signature) => Some(new_signature_provider(signature)?),
+                    None => None,
+                };
+                Ok(HttpPublisher {
+                    cfg: cfg.clone(),
+                    client: build_client(cfg)?,
+                    sign,
+                    batch: Vec::new(),
+                    pending: None,
+                })
+            }
+            _ => Err(Error::IncorrectConfig)
+        }
+    }
+
+    async fn close(&mut self) -> Result<()> {
+        match self.send_batches(true).await? {
+            Some(refused) => Err(refused),
+            None => Ok(()),
+        }
+    }
+
+    async fn connect(&mut self) -> Result<()> {
+        // Requests are independent, there is no connection to set up
+        Ok(())
+    }
+
+    async fn reconnect(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn publish(&mut self, msg: MessageWrapper<'_>) -> Result<()> {
+        // Past this point the message is buffered, so failing would have a retrying caller send it
+        // twice. Refused batches are logged as they are dropped, and held ones are retried on the
+        // next publish or close
+        if self.buffered() >= self.cfg.max_buffered {
+            return Err(Error::HttpBufferFull(self.buffered()))
+        }
+        self.batch.push(serde_json::to_value(&msg)?);
+        if self.pending.is_none() && self.batch.len() < self.batch_size() {
+            debug!("Holding message, {} of {} in batch", self.batch.len(), self.batch_size());
+            return Ok(())
+        }
+        if let Err(e) = self.send_batches(false).await {
+            warn!("Holding {} messages for redelivery: {}", self.buffered(), e);
+        }
+        Ok(())
+    }
+}
+
+fn build_client(cfg: &HttpStreamConfig) -> Result<Client> {
+    let mut headers = HeaderMap::new();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    for (name, value) in &cfg.headers {
+        let name = HeaderName::from_bytes(name.as_bytes()).map_err(|_| Error::IncorrectConfig)?;
+        let value = HeaderValue::from_str(value).map_err(|_| Error::IncorrectConfig)?;
+        headers.insert(name, value);
+    }
+    if let Some(token) = cfg.bearer_token() {
+        let mut value = HeaderValue::from_str(&format!("Bearer {}", token)).map_err(|_| Error::IncorrectConfig)?;
+        value.set_sensitive(true);
+        headers.insert(AUTHORIZATION, value);
+    }
+
+    let mut builder = Client::builder()
+        .default_headers(headers)
+        .timeout(Duration::from_millis(cfg.timeout_ms));
+
+    if let Some(tls) = &cfg.tls {
+        if let Some(ca_path) = &tls.ca_path {
+            let pem = std::fs::read(ca_path).map_err(Error::CaBundleError)?;
+            builder = builder.add_root_certificate(Certificate::from_pem(&pem)?);
+        }
+        match (&tls.cert_path, &tls.key_path) {
+            (Some(cert_path), Some(key_path)) => {
+                let mut pem = std::fs::read(cert_path).map_err(|_| Error::IncorrectConfig)?;
+                pem.extend(std::fs::read(key_path).map_err(|_| Error::IncorrectConfig)?);
+                builder = builder.identity(Identity::from_pem(&pem)?);
+            }
+            (None, None) => {},
+            _ => return Err(Error::IncorrectConfig),
+        }
+    }
+    Ok(builder.build()?)
+}
+
+
+#[cfg(test)]
+mod http_tests {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread::JoinHandle;
+    use alvarium_annotator::{MessageWrapper, Publisher, SignProvider};
+    use crate::config::{SdkInfo, StreamConfig, StreamInfo};
+    use crate::factories::new_signature_provider;
+    use crate::providers::stream_provider::HttpPublisher;
+    use crate::errors::Error;
+
+    struct Request {
+        headers: Vec<(String, String)>,
+        body: Vec<u8>,
+    }
+
+    impl Request {
+        fn header(&self, name: &str) -> Option<&str> {
+            self.headers.iter()
+                .find(|(header, _)| header.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.as_str())
+        }
+    }
+
+    // Answers one request per connection with each status in turn, returning the requests
+    fn serve(statuses: Vec<u16>) -> (String, JoinHandle<Vec<Request>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/annotations", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            statuses.into_iter().map(|status| {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut headers = Vec::new();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let line = line.trim_end();
+                    if line.is_empty() {
+                        break
+                    }
+                    if let Some((name, value)) = line.split_once(": ") {
+                        headers.push((name.to_string(), value.to_string()));
+                    }
+                }
+                let mut request = Request { headers, body: Vec::new() };
+                let length = request.header("content-length").map_or(0, |length| length.parse().unwrap());
+                request.body = vec![0u8; length];
+                reader.read_exact(&mut request.body).unwrap();
+
+                write!(stream, "HTTP/1.1 {} Status\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status).unwrap();
+                request
+            }).collect()
+        });
+        (url, handle)
+    }
+
+    fn http_config(endpoint: &str) -> StreamInfo {
+        let mut info: StreamInfo = serde_json::from_slice(crate::HTTP_TEST_CONFIG_BYTES.as_slice()).unwrap();
+        if let StreamConfig::Http(cfg) = &mut info.config {
+            cfg.endpoints = vec![endpoint.to_string()];
+            cfg.backoff_ms = 10;
+        }
+        info
+    }
+
+    fn message(content: &str) -> MessageWrapper<'_> {
+        MessageWrapper {
+            action: crate::annotations::constants::ACTION_CREATE.clone(),
+            message_type: "AnnotationList",
+            content,
+        }
+    }
+
+    #[tokio::test]
+    async fn http_provider_publish_signed_with_retry() {
+        let sdk_info: SdkInfo = serde_json::from_slice(crate::CONFIG_BYTES.as_slice()).unwrap();
+        let (url, server) = serve(vec![503, 200]);
+
+        let mut publisher = HttpPublisher::new(&http_config(&url)).await.unwrap();
+        publisher.connect().await.unwrap();
+        publisher.publish(message("content")).await.unwrap();
+
+        let requests = server.join().unwrap();
+        assert_eq!(requests.len(), 2);
+        let request = &requests[1];
+        assert_eq!(request.header("authorization"), Some("Bearer A bearer token"));
+        assert_eq!(request.header("x-gateway"), Some("A gateway ID"));
+
+        let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+        assert_eq!(body["content"], "content");
+        let signature = hex::decode(request.header(super::SIGNATURE_HEADER).unwrap()).unwrap();
+        let sign = new_signature_provider(&sdk_info.signature).unwrap();
+        assert!(sign.verify(&request.body, &signature).unwrap());
+    }
+
+    #[tokio::test]
+    async fn http_provider_batches_and_gives_up() {
+        let (url, server) = serve(vec![200, 400]);
+        let mut info = http_config(&url);
+        if let StreamConfig::Http(cfg) = &mut info.config {
+            cfg.batch_size = 2;
+        }
+
+        let mut publisher = HttpPublisher::new(&info).await.unwrap();
+        publisher.publish(message("first")).await.unwrap();
+        publisher.publish(message("second")).await.unwrap();
+        publisher.publish(message("third")).await.unwrap();
+        // The partial batch goes out on close, and a 400 is not retried
+        assert!(matches!(publisher.close().await, Err(Error::HttpEndpointError(_, 400))));
+
+        let requests = server.join().unwrap();
+        let batch: Vec<serde_json::Value> = serde_json::from_slice(&requests[0].body).unwrap();
+        assert_eq!(batch.len(), 2);
+        assert_eq!(batch[1]["content"], "second");
+        let batch: Vec<serde_json::Value> = serde_json::from_slice(&requests[1].body).unwrap();
+        assert_eq!(batch[0]["content"], "third");
+    }
+
+    #[tokio::test]
+    async fn http_provider_retries_only_failed_endpoints() {
+        let (first_url, first) = serve(vec![200, 200]);
+        let (second_url, second) = serve(vec![503, 200, 200]);
+        let mut info = http_config(&first_url);
+        if let StreamConfig::Http(cfg) = &mut info.config {
+            cfg.endpoints.push(second_url);
+            cfg.retries = 0;
+        }
+
+        let mut publisher = HttpPublisher::new(&info).await.unwrap();
+        // Accepted and held for the endpoint that failed
+        publisher.publish(message("first")).await.unwrap();
+        assert_eq!(publisher.buffered(), 1);
+        // The held message goes to the endpoint that failed before the next one is sent
+        publisher.publish(message("second")).await.unwrap();
+
+        let contents = |requests: Vec<Request>| requests.iter()
+            .map(|request| serde_json::from_slice::<serde_json::Value>(&request.body).unwrap()["content"].clone())
+            .collect::<Vec<_>>();
+        assert_eq!(contents(first.join().unwrap()), vec!["first", "second"]);
+        assert_eq!(contents(second.join().unwrap()), vec!["first", "first", "second"]);
+    }
+
+    #[tokio::test]
+    async fn http_provider_drops_refused_batches() {
+        let (url, server) = serve(vec![400, 200]);
+        let mut publisher = HttpPublisher::new(&http_config(&url)).await.unwrap();
+        publisher.publish(message("first")).await.unwrap();
+        // The refused batch is not sent again ahead of the next one
+        assert_eq!(publisher.buffered(), 0);
+        publisher.publish(message("second")).await.unwrap();
+        publisher.close().await.unwrap();
+
+        let requests = server.join().unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&requests[1].body).unwrap();
+        assert_eq!(body["content"], "second");
+    }
+
+    #[tokio::test]
+    async fn http_provider_buffer_capped() {
+        let (url, server) = serve(vec![503]);
+        let mut info = http_config(&url);
+        if let StreamConfig::Http(cfg) = &mut info.config {
+            cfg.batch_size = 0;
+            cfg.max_buffered = 1;
+            cfg.retries = 0;
+        }
+
+        let mut publisher = HttpPublisher::new(&info).await.unwrap();
+        publisher.publish(message("first")).await.unwrap();
+        assert!(matches!(publisher.publish(message("second")).await, Err(Error::HttpBufferFull(1))));
+
+        // A batch size of 0 sends single messages, not one element arrays
+        let requests = server.join().unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+        assert_eq!(body["content"], "first");
+    }
+}
@@ -0,0 +1,182 @@
+use std::time::Duration;
+use crate::config::{KafkaKey, KafkaStreamConfig, StreamConfig, StreamInfo};
+use alvarium_annotator::{MessageWrapper, Publisher};
+use rdkafka::ClientConfig;
+use rdkafka::producer::{FutureProducer, FutureRecord, Producer};
+use log::debug;
+use crate::errors::{Error, Result};
+use super::{action_name, annotation_key};
+
+pub struct KafkaPublisher {
+    cfg: KafkaStreamConfig,
+    producer: FutureProducer,
+}
+
+impl KafkaPublisher {
+    fn timeout(&self) -> Duration {
+        Duration::from_millis(self.cfg.timeout_ms)
+    }
+
+    fn record_key(&self, msg: &MessageWrapper<'_>) -> Result<Option<String>> {
+        match self.cfg.key {
+            KafkaKey::None => Ok(None),
+            KafkaKey::AnnotationKey => Ok(annotation_key(msg.content)),
+            KafkaKey::Action => Ok(Some(action_name(msg)?)),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Publisher for KafkaPublisher {
+    type StreamConfig = StreamInfo;
+    type Error = crate::errors::Error;
+    async fn new(cfg: &StreamInfo) -> Result<Self> {
+        match &cfg.config {
+            StreamConfig::Kafka(cfg) => {
+                Ok(KafkaPublisher {
+                    cfg: cfg.clone(),
+                    producer: client_config(cfg).create()?,
+                })
+            }
+            _ => Err(Error::IncorrectConfig)
+        }
+    }
+
+    async fn close(&mut self) -> Result<()> {
+        // Flushing waits on the brokers for up to the timeout, off the runtime's threads
+        let (producer, timeout) = (self.producer.clone(), self.timeout());
+        tokio::task::spawn_blocking(move || producer.flush(timeout))
+            .await
+            .map_err(|e| Error::External(Box::new(e)))??;
+        Ok(())
+    }
+
+    async fn connect(&mut self) -> Result<()> {
+        // Fetching the topic metadata is the first point the brokers are actually contacted
+        let producer = self.producer.clone();
+        let (topic, timeout) = (self.cfg.topic.clone(), self.timeout());
+        tokio::task::spawn_blocking(move || producer.client().fetch_metadata(Some(&topic), timeout))
+            .await
+            .map_err(|e| Error::External(Box::new(e)))??;
+        Ok(())
+    }
+
+    async fn reconnect(&mut self) -> Result<()> {
+        // librdkafka reconnects to the brokers by itself
+        Ok(())
+    }
+
+    async fn publish(&mut self, msg: MessageWrapper<'_>) -> Result<()> {
+        let bytes = serde_json::to_vec(&msg)?;
+        let key = self.record_key(&msg)?;
+
+        let mut record: FutureRecord<'_, str, [u8]> = FutureRecord::to(&self.cfg.topic).payload(&bytes);
+        if let Some(key) = &key {
+            record = record.key(key.as_str());
+        }
+
+        debug!("Producing to kafka topic {} with key {:?}", self.cfg.topic, key);
+        match self.producer.send(record, self.timeout()).await {
+            Ok(_) => Ok(()),
+            Err((e, _)) => Err(e.into()),
+        }
+    }
+}
+
+fn client_config(cfg: &KafkaStreamConfig) -> ClientConfig {
+    let mut config = ClientConfig::new();
+    config.set("bootstrap.servers", cfg.brokers.join(","))
+        .set("acks", &cfg.acks)
+        .set("enable.idempotence", cfg.idempotence.to_string())
+        .set("linger.ms", cfg.linger_ms.to_string())
+        .set("message.timeout.ms", cfg.timeout_ms.to_string());
+    if let Some(client_id) = &cfg.client_id {
+        config.set("client.id", client_id);
+    }
+
+    let protocol = match (&cfg.tls, &cfg.sasl) {
+        (Some(_), Some(_)) => "SASL_SSL",
+        (None, Some(_)) => "SASL_PLAINTEXT",
+        (Some(_), None) => "SSL",
+        (None, None) => "PLAINTEXT",
+    };
+    config.set("security.protocol", protocol);
+
+    if let Some(tls) = &cfg.tls {
+        for (key, path) in [
+            ("ssl.ca.location", &tls.ca_path),
+            ("ssl.certificate.location", &tls.cert_path),
+            ("ssl.key.location", &tls.key_path),
+        ] {
+            if let Some(path) = path {
+                config.set(key, path);
+            }
+        }
+    }
+    if let Some(sasl) = &cfg.sasl {
+        config.set("sasl.mechanisms", &sasl.mechanism)
+            .set("sasl.username", &sasl.username)
+            .set("sasl.password", sasl.password());
+    }
+    config
+}
+
+
+#[cfg(test)]
+mod kafka_tests {
+    use alvarium_annotator::{Annotator, AnnotationList, MessageWrapper, Publisher};
+    use crate::annotations::PkiAnnotator;
+    use crate::config::{KafkaKey, SdkInfo, Signable, StreamConfig, StreamInfo};
+    use crate::providers::stream_provider::KafkaPublisher;
+    use super::client_config;
+
+    fn kafka_config() -> StreamInfo {
+        serde_json::from_slice(crate::KAFKA_TEST_CONFIG_BYTES.as_slice()).unwrap()
+    }
+
+    #[test]
+    fn producer_settings() {
+        let mut info = kafka_config();
+        if let StreamConfig::Kafka(cfg) = &mut info.config {
+            assert_eq!(cfg.key, KafkaKey::AnnotationKey);
+            let config = client_config(cfg);
+            assert_eq!(config.get("acks"), Some("all"));
+            assert_eq!(config.get("enable.idempotence"), Some("true"));
+            assert_eq!(config.get("security.protocol"), Some("PLAINTEXT"));
+
+            cfg.sasl = serde_json::from_str(r#"{"mechanism": "SCRAM-SHA-512", "username": "alvarium", "password": "secret"}"#).unwrap();
+            cfg.tls = serde_json::from_str(r#"{"caPath": "/etc/ssl/ca.pem"}"#).unwrap();
+            let config = client_config(cfg);
+            assert_eq!(config.get("security.protocol"), Some("SASL_SSL"));
+            assert_eq!(config.get("ssl.ca.location"), Some("/etc/ssl/ca.pem"));
+            assert_eq!(config.get("sasl.password"), Some("secret"));
+        } else {
+            panic!("expected a kafka stream config")
+        }
+    }
+
+    // Needs a broker at localhost:9092, e.g. a single node one started with
+    // `docker run -p 9092:9092 apache/kafka:3.7.0`
+    #[tokio::test]
+    #[ignore = "needs a Kafka broker at localhost:9092"]
+    async fn kafka_provider_publish() {
+        let sdk_info: SdkInfo = serde_json::from_slice(crate::CONFIG_BYTES.as_slice()).unwrap();
+        let mut publisher = KafkaPublisher::new(&kafka_config()).await.unwrap();
+        publisher.connect().await.unwrap();
+
+        let sig = hex::encode([0u8; crypto::signatures::ed25519::SIGNATURE_LENGTH]);
+        let signable = Signable::new("A packet to send to subscribers".to_string(), sig);
+
+        let mut list = AnnotationList { items: vec![] };
+        let mut pki_annotator = PkiAnnotator::new(&sdk_info).unwrap();
+        list.items.push(pki_annotator.annotate(&serde_json::to_vec(&signable).unwrap()).unwrap());
+
+        let data = MessageWrapper {
+            action: crate::annotations::constants::ACTION_CREATE.clone(),
+            message_type: std::any::type_name::<AnnotationList>(),
+            content: &base64::encode(&serde_json::to_vec(&list).unwrap()),
+        };
+        publisher.publish(data).await.unwrap();
+        publisher.close().await.unwrap();
+    }
+}
@@ -1,12 +1,88 @@
+#[cfg(feature = "amqp")]
+mod amqp;
+#[cfg(feature = "coap")]
+mod coap;
+mod http;
 mod iota;
+#[cfg(feature = "kafka")]
+mod kafka;
 mod mqtt;
+mod mqtt_v5;
+#[cfg(feature = "nats")]
+mod nats;
+#[cfg(feature = "redis")]
+mod redis_streams;
 
+#[cfg(feature = "amqp")]
+pub use amqp::AmqpPublisher;
+#[cfg(feature = "coap")]
+pub use coap::CoapPublisher;
+pub use http::HttpPublisher;
 pub use iota::IotaPublisher;
+#[cfg(feature = "kafka")]
+pub use kafka::KafkaPublisher;
 pub use mqtt::MqttPublisher;
+pub use mqtt_v5::MqttV5Publisher;
+#[cfg(feature = "nats")]
+pub use nats::NatsPublisher;
+#[cfg(feature = "redis")]
+pub use redis_streams::RedisPublisher;
+
+use alvarium_annotator::MessageWrapper;
+use serde_json::Value;
+use crate::errors::Result;
 
 
 // TODO: Implement publisher for enum
 pub enum PublisherWrap {
     Iota(IotaPublisher),
     Mqtt(MqttPublisher),
+    MqttV5(MqttV5Publisher),
+    #[cfg(feature = "kafka")]
+    Kafka(KafkaPublisher),
+    #[cfg(feature = "nats")]
+    Nats(NatsPublisher),
+    Http(HttpPublisher),
+    #[cfg(feature = "amqp")]
+    Amqp(AmqpPublisher),
+    #[cfg(feature = "redis")]
+    Redis(RedisPublisher),
+    #[cfg(feature = "coap")]
+    Coap(CoapPublisher),
+}
+
+/// The action a message was published for, as it appears on the wire (e.g. `create`)
+pub(crate) fn action_name(msg: &MessageWrapper<'_>) -> Result<String> {
+    match serde_json::to_value(&msg.action)? {
+        Value::String(action) => Ok(action),
+        other => Ok(other.to_string()),
+    }
+}
+
+/// The key of the data annotated in a published message, read from the base64 encoded content.
+/// Annotation lists are keyed by their first item, as every item annotates the same data
+pub(crate) fn annotation_key(content: &str) -> Option<String> {
+    let decoded = base64::decode(content).ok()?;
+    let value: Value = serde_json::from_slice(&decoded).ok()?;
+    let annotation = match value.get("items") {
+        Some(items) => items.get(0)?,
+        None => &value,
+    };
+    annotation.get("key")?.as_str().map(str::to_string)
+}
+
+
+#[cfg(test)]
+mod stream_provider_tests {
+    use super::annotation_key;
+
+    #[test]
+    fn annotation_key_from_content() {
+        let list = base64::encode(r#"{"items":[{"key":"first"},{"key":"second"}]}"#);
+        let lineage = base64::encode(r#"{"key":"child","parents":["parent"]}"#);
+        assert_eq!(annotation_key(&list).as_deref(), Some("first"));
+        assert_eq!(annotation_key(&lineage).as_deref(), Some("child"));
+        assert_eq!(annotation_key(&base64::encode(r#"{"items":[]}"#)), None);
+        assert_eq!(annotation_key("not base64!"), None);
+    }
 }
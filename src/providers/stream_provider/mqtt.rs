@@ -1,5 +1,11 @@
-use crate::config::{MqttStreamConfig, StreamConfig, StreamInfo};
-use rumqttc::{AsyncClient, ConnectionError, EventLoop, MqttOptions, QoS};
+#[cfg(feature = "rustls")]
+use std::sync::Arc;
+#[cfg(feature = "rustls")]
+use crate::config::MqttTlsConfig;
+use crate::config::{MqttStreamConfig, MqttVersion, StreamConfig, StreamInfo};
+use rumqttc::{AsyncClient, ConnectionError, EventLoop, MqttOptions, QoS, Transport};
+#[cfg(feature = "rustls")]
+use rumqttc::TlsConfiguration;
 use alvarium_annotator::{MessageWrapper, Publisher};
 use log::{debug, warn};
 use crate::errors::{Error, Result};
@@ -17,8 +23,8 @@ impl Publisher for MqttPublisher {
     type Error = crate::errors::Error;
     async fn new(cfg: &StreamInfo) -> Result<Self> {
         match &cfg.config {
-            StreamConfig::MQTT(cfg)=> {
-                let (client, connection) = setup_client(&cfg);
+            StreamConfig::MQTT(cfg) if cfg.version == MqttVersion::V311 => {
+                let (client, connection) = setup_client(cfg)?;
                 Ok(MqttPublisher {
                     cfg: cfg.clone(),
                     client,
@@ -47,17 +53,25 @@ impl Publisher for MqttPublisher {
         }
 
         debug!("Making new client");
-        let (client, mut connection) = setup_client(&self.cfg);
+        let (client, mut connection) = setup_client(&self.cfg)?;
         debug!("Polling for error in reconnection");
-        if let Err(ConnectionError::ConnectionRefused(e)) = connection.poll().await {
-            warn!("Connection Error: {:?}", e);
-            return Err(e.into())
+        match connection.poll().await {
+            Ok(_) => {}
+            Err(ConnectionError::ConnectionRefused(e)) => {
+                warn!("Connection Error: {:?}", e);
+                return Err(e.into())
+            }
+            // Network and TLS failures leave the event loop unconnected just the same
+            Err(e) => {
+                warn!("Connection Error: {:?}", e);
+                return Err(e.into())
+            }
         }
 
         self.client = client;
         self.connection = connection;
 
-        let qos = qos(self.cfg.qos);
+        let qos: QoS = qos(self.cfg.qos);
         for topic in &self.cfg.topics {
             debug!("Subscribing to topic: {}", topic);
             self.client.subscribe(topic, qos).await?
@@ -79,20 +93,129 @@ impl Publisher for MqttPublisher {
     }
 }
 
-fn setup_client(cfg: &MqttStreamConfig) -> (AsyncClient, EventLoop) {
-    let mut mqtt_options = MqttOptions::new(&cfg.client_id, &cfg.provider.host, cfg.provider.port as u16);
+fn setup_client(cfg: &MqttStreamConfig) -> Result<(AsyncClient, EventLoop)> {
+    let transport = transport(cfg)?;
+    let mut mqtt_options = MqttOptions::new(&cfg.client_id, broker(cfg, &transport), cfg.provider.port as u16);
     mqtt_options.set_keep_alive(tokio::time::Duration::from_secs(cfg.keep_alive as u64));
     mqtt_options.set_credentials(&cfg.user, cfg.password());
     mqtt_options.set_clean_session(cfg.cleanness);
+    mqtt_options.set_transport(transport);
+
+    Ok(AsyncClient::new(mqtt_options, cfg.cap))
+}
+
+// Websocket transports take the broker as a URL, which carries the port
+pub(super) fn broker(cfg: &MqttStreamConfig, transport: &Transport) -> String {
+    match transport {
+        Transport::Tcp | Transport::Tls(_) => cfg.provider.host.clone(),
+        _ => format!("{}{}", cfg.provider.uri(), cfg.ws_path),
+    }
+}
+
+pub(super) fn transport(cfg: &MqttStreamConfig) -> Result<Transport> {
+    match cfg.provider.protocol.as_str() {
+        // TLS settings on a plaintext transport are a misconfiguration, not something to ignore
+        "tcp" | "ws" if cfg.tls.is_some() => Err(Error::IncorrectConfig),
+        "tcp" => Ok(Transport::Tcp),
+        "ws" => Ok(Transport::Ws),
+        #[cfg(feature = "rustls")]
+        "tls" | "ssl" | "mqtts" => Ok(Transport::Tls(tls_configuration(cfg.tls.as_ref())?)),
+        #[cfg(feature = "rustls")]
+        "wss" => Ok(Transport::Wss(tls_configuration(cfg.tls.as_ref())?)),
+        #[cfg(not(feature = "rustls"))]
+        "tls" | "ssl" | "mqtts" | "wss" => Err(Error::FeatureNotEnabled("rustls")),
+        _ => Err(Error::IncorrectConfig),
+    }
+}
+
+#[cfg(feature = "rustls")]
+fn tls_configuration(tls: Option<&MqttTlsConfig>) -> Result<TlsConfiguration> {
+    let tls = tls.cloned().unwrap_or_default();
+    let mut root_store = rustls::RootCertStore::empty();
+    match &tls.ca_path {
+        Some(ca_path) => {
+            let (added, _) = root_store.add_parsable_certificates(&read_certs(ca_path)?);
+            if added == 0 {
+                return Err(Error::InvalidCertificate(format!("no CA certificates in {}", ca_path)))
+            }
+        }
+        None => root_store.add_trust_anchors(
+            webpki_roots::TLS_SERVER_ROOTS
+                .0
+                .iter()
+                .map(|ta| {
+                    rustls::OwnedTrustAnchor::from_subject_spki_name_constraints(
+                        ta.subject,
+                        ta.spki,
+                        ta.name_constraints,
+                    )
+                })
+        ),
+    }
+
+    let builder = rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(root_store);
+    let mut config = match (&tls.cert_path, &tls.key_path) {
+        (Some(cert_path), Some(key_path)) => {
+            let certs = read_certs(cert_path)?.into_iter().map(rustls::Certificate).collect();
+            builder.with_client_auth_cert(certs, read_private_key(key_path)?)
+                .map_err(|e| Error::InvalidCertificate(e.to_string()))?
+        }
+        (None, None) => builder.with_no_client_auth(),
+        _ => return Err(Error::IncorrectConfig),
+    };
+    config.alpn_protocols = tls.alpn.iter().map(|protocol| protocol.as_bytes().to_vec()).collect();
+
+    Ok(TlsConfiguration::Rustls(Arc::new(config)))
+}
+
+#[cfg(feature = "rustls")]
+fn read_certs(path: &str) -> Result<Vec<Vec<u8>>> {
+    let pem = std::fs::read(path).map_err(Error::CaBundleError)?;
+    rustls_pemfile::certs(&mut pem.as_slice()).map_err(Error::CaBundleError)
+}
+
+// First RSA, PKCS#8 or SEC1 key in a PEM file
+#[cfg(feature = "rustls")]
+fn read_private_key(path: &str) -> Result<rustls::PrivateKey> {
+    let pem = std::fs::read(path).map_err(Error::CaBundleError)?;
+    let mut reader = pem.as_slice();
+    while let Some(item) = rustls_pemfile::read_one(&mut reader).map_err(Error::CaBundleError)? {
+        match item {
+            rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::ECKey(key) => return Ok(rustls::PrivateKey(key)),
+            _ => continue,
+        }
+    }
+    Err(Error::InvalidCertificate(format!("no private key in {}", path)))
+}
+
+/// The QoS levels of either protocol version, which rumqttc keeps as separate types
+pub(super) trait QosLevel {
+    const AT_MOST_ONCE: Self;
+    const AT_LEAST_ONCE: Self;
+    const EXACTLY_ONCE: Self;
+}
 
-    AsyncClient::new(mqtt_options, cfg.cap)
+impl QosLevel for QoS {
+    const AT_MOST_ONCE: Self = QoS::AtMostOnce;
+    const AT_LEAST_ONCE: Self = QoS::AtLeastOnce;
+    const EXACTLY_ONCE: Self = QoS::ExactlyOnce;
 }
 
-fn qos(qos: u8) -> QoS {
+impl QosLevel for rumqttc::v5::mqttbytes::QoS {
+    const AT_MOST_ONCE: Self = rumqttc::v5::mqttbytes::QoS::AtMostOnce;
+    const AT_LEAST_ONCE: Self = rumqttc::v5::mqttbytes::QoS::AtLeastOnce;
+    const EXACTLY_ONCE: Self = rumqttc::v5::mqttbytes::QoS::ExactlyOnce;
+}
+
+pub(super) fn qos<Q: QosLevel>(qos: u8) -> Q {
     match qos {
-        1 => QoS::AtLeastOnce,
-        2 => QoS::ExactlyOnce,
-        _ => QoS::AtMostOnce,
+        1 => Q::AT_LEAST_ONCE,
+        2 => Q::EXACTLY_ONCE,
+        _ => Q::AT_MOST_ONCE,
     }
 }
 
@@ -102,8 +225,11 @@ mod mqtt_tests {
     use alvarium_annotator::{Annotator, AnnotationList, MessageWrapper, Publisher};
     use log::info;
     use crate::annotations::PkiAnnotator;
-    use crate::config::{SdkInfo, Signable, StreamInfo};
+    use rumqttc::Transport;
+    use crate::config::{MqttTlsConfig, SdkInfo, Signable, StreamConfig, StreamInfo};
+    use crate::errors::Error;
     use crate::providers::stream_provider::MqttPublisher;
+    use super::transport;
 
     #[tokio::test]
     async fn new_mqtt_provider() {
@@ -143,4 +269,57 @@ mod mqtt_tests {
         publisher.publish(data).await.unwrap();
         publisher.close().await.unwrap();
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn mqtt_connect_failure_surfaced() {
+        // A port nothing is listening on
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let mut stream_info: StreamInfo = serde_json::from_slice(crate::MQTT_TEST_CONFIG_BYTES.as_slice()).unwrap();
+        if let StreamConfig::MQTT(cfg) = &mut stream_info.config {
+            cfg.provider = crate::config::UrlInfo { host: "127.0.0.1".to_string(), port: port as usize, protocol: "tcp".to_string() };
+            cfg.tls = None;
+        }
+
+        let mut publisher = MqttPublisher::new(&stream_info).await.unwrap();
+        assert!(matches!(publisher.connect().await, Err(Error::MqttConnectionError(_))));
+    }
+
+    fn mqtt_config(protocol: &str, tls: Option<MqttTlsConfig>) -> crate::config::MqttStreamConfig {
+        let stream_info: StreamInfo = serde_json::from_slice(crate::MQTT_TEST_CONFIG_BYTES.as_slice()).unwrap();
+        match stream_info.config {
+            StreamConfig::MQTT(mut cfg) => {
+                cfg.provider.protocol = protocol.to_string();
+                cfg.tls = tls;
+                cfg
+            }
+            _ => panic!("expected an mqtt stream config"),
+        }
+    }
+
+    #[cfg(feature = "rustls")]
+    #[test]
+    fn mqtt_transport_follows_protocol() {
+        assert!(matches!(transport(&mqtt_config("tcp", None)), Ok(Transport::Tcp)));
+        assert!(matches!(transport(&mqtt_config("ws", None)), Ok(Transport::Ws)));
+        for protocol in ["tls", "ssl", "mqtts"] {
+            assert!(matches!(transport(&mqtt_config(protocol, None)), Ok(Transport::Tls(_))));
+        }
+        let alpn = MqttTlsConfig { alpn: vec!["x-amzn-mqtt-ca".to_string()], ..Default::default() };
+        assert!(matches!(transport(&mqtt_config("wss", Some(alpn))), Ok(Transport::Wss(_))));
+
+        assert!(matches!(transport(&mqtt_config("udp", None)), Err(Error::IncorrectConfig)));
+    }
+
+    #[cfg(feature = "rustls")]
+    #[test]
+    fn mqtt_tls_misconfiguration() {
+        // TLS settings are never silently dropped in favour of plaintext
+        assert!(matches!(transport(&mqtt_config("tcp", Some(MqttTlsConfig::default()))), Err(Error::IncorrectConfig)));
+
+        let missing_ca = MqttTlsConfig { ca_path: Some("resources/missing_ca.pem".to_string()), ..Default::default() };
+        assert!(matches!(transport(&mqtt_config("tls", Some(missing_ca))), Err(Error::CaBundleError(_))));
+
+        let cert_only = MqttTlsConfig { cert_path: Some("resources/client.pem".to_string()), ..Default::default() };
+        assert!(matches!(transport(&mqtt_config("tls", Some(cert_only))), Err(Error::IncorrectConfig)));
+    }
+}
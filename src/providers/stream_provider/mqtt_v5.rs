@@ -0,0 +1,381 @@
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use crate::config::{MqttStreamConfig, MqttVersion, StreamConfig, StreamInfo};
+use rumqttc::Outgoing;
+use rumqttc::v5::{AsyncClient, ConnectionError, Event, EventLoop, MqttOptions};
+use rumqttc::v5::mqttbytes::QoS;
+use rumqttc::v5::mqttbytes::v5::{Packet, PubAckReason, PubCompReason, PubRecReason, PublishProperties};
+use alvarium_annotator::{MessageWrapper, Publisher};
+use log::{debug, warn};
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+use tokio::task::JoinHandle;
+use crate::errors::{Error, Result};
+use super::action_name;
+use super::mqtt::{broker, qos, transport};
+
+// Payload format indicator for UTF-8 encoded payloads
+const UTF8_PAYLOAD: u8 = 1;
+
+pub struct MqttV5Publisher {
+    cfg: MqttStreamConfig,
+    client: AsyncClient,
+    /// Polls the event loop for as long as the connection lasts, keeping it alive while idle
+    driver: Option<JoinHandle<()>>,
+    /// Events from the driver that publishing and closing wait on
+    events: Option<UnboundedReceiver<std::result::Result<Event, ConnectionError>>>,
+    /// Topic aliases for the current connection
+    aliases: HashMap<String, u16>,
+    /// Aliased topics already sent in full, so the broker can resolve their alias
+    announced: HashSet<String>,
+}
+
+impl MqttV5Publisher {
+    fn properties(&self, msg: &MessageWrapper<'_>, topic: &str) -> Result<PublishProperties> {
+        Ok(PublishProperties {
+            payload_format_indicator: Some(UTF8_PAYLOAD),
+            message_expiry_interval: self.cfg.v5.message_expiry_secs,
+            topic_alias: self.aliases.get(topic).copied(),
+            content_type: Some(self.cfg.v5.content_type.clone()),
+            user_properties: vec![
+                ("action".to_string(), action_name(msg)?),
+                ("messageType".to_string(), msg.message_type.to_string()),
+            ],
+            ..Default::default()
+        })
+    }
+
+    fn is_connected(&self) -> bool {
+        self.driver.as_ref().map_or(false, |driver| !driver.is_finished())
+    }
+
+    async fn next_event(&mut self) -> Result<Event> {
+        let event = match self.events.as_mut() {
+            Some(events) => events.recv().await,
+            None => None,
+        };
+        match event {
+            Some(Ok(event)) => Ok(event),
+            Some(Err(e)) => Err(e.into()),
+            None => Err(Error::MqttConnectReturnError("event loop stopped".to_string())),
+        }
+    }
+
+    // Waits until each of `count` publishes is written out for QoS 0, or has its acknowledgement
+    // flow finished by packet ID otherwise, failing with the first reason code the broker
+    // rejected one with only once none are outstanding
+    async fn await_delivery(&mut self, count: usize) -> Result<()> {
+        let qos: QoS = qos(self.cfg.qos);
+        let mut sent = 0;
+        let mut outstanding = HashSet::new();
+        let mut rejected = None;
+        while sent < count || !outstanding.is_empty() {
+            let event = match self.next_event().await {
+                Ok(event) => event,
+                Err(e) => {
+                    self.stop();
+                    return Err(e)
+                }
+            };
+            match event {
+                Event::Outgoing(Outgoing::Publish(pkid)) => {
+                    sent += 1;
+                    if qos != QoS::AtMostOnce {
+                        outstanding.insert(pkid);
+                    }
+                }
+                Event::Incoming(Packet::PubAck(ack)) if outstanding.remove(&ack.pkid) => {
+                    if !matches!(ack.reason, PubAckReason::Success | PubAckReason::NoMatchingSubscribers) {
+                        rejected.get_or_insert(format!("packet {} ({:?})", ack.pkid, ack.reason));
+                    }
+                }
+                // A rejected PubRec ends the flow, an accepted one waits on its PubComp
+                Event::Incoming(Packet::PubRec(rec)) if outstanding.contains(&rec.pkid) => {
+                    if !matches!(rec.reason, PubRecReason::Success | PubRecReason::NoMatchingSubscribers) {
+                        outstanding.remove(&rec.pkid);
+                        rejected.get_or_insert(format!("packet {} ({:?})", rec.pkid, rec.reason));
+                    }
+                }
+                Event::Incoming(Packet::PubComp(comp)) if outstanding.remove(&comp.pkid) => {
+                    if !matches!(comp.reason, PubCompReason::Success) {
+                        rejected.get_or_insert(format!("packet {} ({:?})", comp.pkid, comp.reason));
+                    }
+                }
+                Event::Incoming(Packet::Disconnect(disconnect)) => {
+                    self.stop();
+                    return Err(Error::MqttConnectReturnError(format!("{:?}", disconnect.reason_code)))
+                }
+                _ => {}
+            }
+        }
+        match rejected {
+            Some(rejected) => Err(Error::PublishNotConfirmed(rejected)),
+            None => Ok(()),
+        }
+    }
+
+    fn stop(&mut self) {
+        if let Some(driver) = self.driver.take() {
+            driver.abort();
+        }
+        self.events = None;
+    }
+}
+
+// Only the events publishing and closing wait on are passed along, so nothing piles up while idle
+fn is_forwarded(event: &Event) -> bool {
+    matches!(event,
+        Event::Outgoing(Outgoing::Publish(_)) | Event::Outgoing(Outgoing::Disconnect)
+        | Event::Incoming(Packet::PubAck(_)) | Event::Incoming(Packet::PubRec(_))
+        | Event::Incoming(Packet::PubComp(_)) | Event::Incoming(Packet::Disconnect(_)))
+}
+
+#[async_trait::async_trait]
+impl Publisher for MqttV5Publisher {
+    type StreamConfig = StreamInfo;
+    type Error = crate::errors::Error;
+    async fn new(cfg: &StreamInfo) -> Result<Self> {
+        match &cfg.config {
+            StreamConfig::MQTT(cfg) if cfg.version == MqttVersion::V5 => {
+                // The event loop is made on connecting, until then publishing through the client fails
+                let (client, _) = setup_client(cfg)?;
+                Ok(MqttV5Publisher {
+                    cfg: cfg.clone(),
+                    client,
+                    driver: None,
+                    events: None,
+                    aliases: HashMap::new(),
+                    announced: HashSet::new(),
+                })
+            }
+            _ => Err(Error::IncorrectConfig)
+        }
+    }
+
+    async fn close(&mut self) -> Result<()> {
+        if self.is_connected() {
+            self.client.disconnect().await?;
+            // Waits until the disconnect has gone out
+            while let Ok(event) = self.next_event().await {
+                if let Event::Outgoing(Outgoing::Disconnect) = event {
+                    break
+                }
+            }
+        }
+        self.stop();
+        Ok(())
+    }
+
+    async fn connect(&mut self) -> Result<()> {
+        let (client, mut connection) = setup_client(&self.cfg)?;
+        let broker_alias_max = loop {
+            match connection.poll().await {
+                Ok(Event::Incoming(Packet::ConnAck(connack))) => {
+                    break connack.properties.and_then(|properties| properties.topic_alias_max).unwrap_or(0)
+                }
+                Ok(_) => continue,
+                Err(ConnectionError::ConnectionRefused(code)) => {
+                    warn!("Connection Error: {:?}", code);
+                    return Err(code.into())
+                }
+                Err(e) => return Err(e.into()),
+            }
+        };
+
+        // Aliases do not outlive the connection they were agreed on
+        self.aliases = assign_aliases(&self.cfg.topics, self.cfg.v5.topic_aliases.min(broker_alias_max));
+        self.announced.clear();
+        self.stop();
+        let (sender, events) = mpsc::unbounded_channel();
+        self.driver = Some(tokio::spawn(async move {
+            loop {
+                let event = connection.poll().await;
+                // The event loop would reconnect on the next poll, the publisher does that instead
+                let failed = event.is_err();
+                let forward = event.as_ref().map_or(true, is_forwarded);
+                if (forward && sender.send(event).is_err()) || failed {
+                    break
+                }
+            }
+        }));
+        self.client = client;
+        self.events = Some(events);
+        Ok(())
+    }
+
+    async fn reconnect(&mut self) -> Result<()> {
+        if self.is_connected() {
+            return Ok(())
+        }
+        debug!("Reconnecting to mqtt v5 broker");
+        self.connect().await
+    }
+
+    async fn publish(&mut self, msg: MessageWrapper<'_>) -> Result<()> {
+        self.reconnect().await?;
+        let bytes = serde_json::to_vec(&msg)?;
+        for topic in &self.cfg.topics {
+            let properties = self.properties(&msg, topic)?;
+            let alias = properties.topic_alias;
+            // Once the broker has the alias, the topic name can be left out
+            let name = match alias.is_some() && self.announced.contains(topic) {
+                true => String::new(),
+                false => topic.clone(),
+            };
+            debug!("Posting to mqtt v5 stream at topic {} with alias {:?}", topic, alias);
+            self.client.publish_with_properties(name, qos::<QoS>(self.cfg.qos), true, bytes.clone(), properties).await?;
+            if alias.is_some() {
+                self.announced.insert(topic.clone());
+            }
+        }
+        let timeout = Duration::from_millis(self.cfg.v5.timeout_ms);
+        let delivered = tokio::time::timeout(timeout, self.await_delivery(self.cfg.topics.len())).await;
+        match delivered {
+            Ok(delivered) => delivered,
+            Err(_) => {
+                // Late acknowledgements would be counted against the next publish, the connection
+                // is dropped for a fresh one instead
+                self.stop();
+                Err(Error::PublishNotConfirmed(format!("{:?} (no acknowledgement within {:?})", self.cfg.topics, timeout)))
+            }
+        }
+    }
+}
+
+fn setup_client(cfg: &MqttStreamConfig) -> Result<(AsyncClient, EventLoop)> {
+    let transport = transport(cfg)?;
+    let mut mqtt_options = MqttOptions::new(&cfg.client_id, broker(cfg, &transport), cfg.provider.port as u16);
+    mqtt_options.set_keep_alive(tokio::time::Duration::from_secs(cfg.keep_alive as u64));
+    mqtt_options.set_credentials(&cfg.user, cfg.password());
+    mqtt_options.set_clean_start(cfg.cleanness);
+    mqtt_options.set_transport(transport);
+
+    Ok(AsyncClient::new(mqtt_options, cfg.cap))
+}
+
+/// Numbers topics from 1 in order, leaving any past `max` without an alias
+fn assign_aliases(topics: &[String], max: u16) -> HashMap<String, u16> {
+    topics.iter()
+        .zip(1..=max)
+        .map(|(topic, alias)| (topic.clone(), alias))
+        .collect()
+}
+
+
+#[cfg(test)]
+mod mqtt_v5_tests {
+    use alvarium_annotator::{AnnotationList, MessageWrapper, Publisher};
+    use crate::config::{MqttVersion, StreamConfig, StreamInfo};
+    use crate::providers::stream_provider::MqttV5Publisher;
+    use rumqttc::Outgoing;
+    use rumqttc::v5::Event;
+    use rumqttc::v5::mqttbytes::v5::{Packet, PingResp, PubAck, PubAckReason};
+    use std::io::{Read, Write};
+    use crate::errors::Error;
+    use super::{assign_aliases, is_forwarded, UTF8_PAYLOAD};
+
+    fn mqtt_v5_stream_info() -> StreamInfo {
+        serde_json::from_slice(crate::MQTT_V5_TEST_CONFIG_BYTES.as_slice()).unwrap()
+    }
+
+    #[test]
+    fn topic_aliases_capped() {
+        let topics = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        let aliases = assign_aliases(&topics, 2);
+        assert_eq!(aliases.get("a"), Some(&1));
+        assert_eq!(aliases.get("b"), Some(&2));
+        assert_eq!(aliases.get("c"), None);
+        assert!(assign_aliases(&topics, 0).is_empty());
+    }
+
+    #[test]
+    fn idle_events_not_forwarded() {
+        assert!(!is_forwarded(&Event::Outgoing(Outgoing::PingReq)));
+        assert!(!is_forwarded(&Event::Incoming(Packet::PingResp(PingResp))));
+        assert!(is_forwarded(&Event::Outgoing(Outgoing::Publish(1))));
+        let ack = PubAck { pkid: 1, reason: PubAckReason::Success, properties: None };
+        assert!(is_forwarded(&Event::Incoming(Packet::PubAck(ack))));
+    }
+
+    #[tokio::test]
+    async fn mqtt_v5_properties() {
+        let mut publisher = MqttV5Publisher::new(&mqtt_v5_stream_info()).await.unwrap();
+        publisher.aliases = assign_aliases(&publisher.cfg.topics, 1);
+
+        let data = MessageWrapper {
+            action: crate::annotations::constants::ACTION_CREATE.clone(),
+            message_type: std::any::type_name::<AnnotationList>(),
+            content: "content",
+        };
+        let properties = publisher.properties(&data, "alvarium/Topic1").unwrap();
+        assert_eq!(properties.payload_format_indicator, Some(UTF8_PAYLOAD));
+        assert_eq!(properties.message_expiry_interval, Some(3600));
+        assert_eq!(properties.topic_alias, Some(1));
+        assert_eq!(properties.content_type.as_deref(), Some("application/json"));
+        assert!(properties.user_properties.contains(&("action".to_string(), "create".to_string())));
+        assert!(properties.user_properties.contains(&("messageType".to_string(), data.message_type.to_string())));
+        assert_eq!(publisher.properties(&data, "alvarium/Topic2").unwrap().topic_alias, None);
+    }
+
+    #[tokio::test]
+    async fn mqtt_v5_publish_times_out_without_ack() {
+        // Accepts the connection and then never acknowledges anything
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            let mut buf = vec![0u8; 1024];
+            socket.read(&mut buf).unwrap();
+            // CONNACK, no session present, success, no properties
+            socket.write_all(&[0x20, 0x03, 0x00, 0x00, 0x00]).unwrap();
+            while socket.read(&mut buf).map_or(false, |read| read > 0) {}
+        });
+
+        let mut stream_info = mqtt_v5_stream_info();
+        if let StreamConfig::MQTT(cfg) = &mut stream_info.config {
+            cfg.provider = crate::config::UrlInfo { host: "127.0.0.1".to_string(), port: port as usize, protocol: "tcp".to_string() };
+            cfg.v5.timeout_ms = 200;
+        }
+        let mut publisher = MqttV5Publisher::new(&stream_info).await.unwrap();
+        publisher.connect().await.unwrap();
+
+        let data = MessageWrapper {
+            action: crate::annotations::constants::ACTION_CREATE.clone(),
+            message_type: std::any::type_name::<AnnotationList>(),
+            content: "content",
+        };
+        assert!(matches!(publisher.publish(data).await, Err(Error::PublishNotConfirmed(_))));
+        // A fresh connection is made for the next publish
+        assert!(!publisher.is_connected());
+    }
+
+    #[tokio::test]
+    async fn mqtt_v5_rejects_v3_config() {
+        let mut stream_info = mqtt_v5_stream_info();
+        if let StreamConfig::MQTT(cfg) = &mut stream_info.config {
+            cfg.version = MqttVersion::V311;
+        }
+        assert!(MqttV5Publisher::new(&stream_info).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn mqtt_v5_provider_publish() {
+        let mut publisher = MqttV5Publisher::new(&mqtt_v5_stream_info()).await.unwrap();
+        publisher.connect().await.unwrap();
+
+        let content = base64::encode(r#"{"items":[]}"#);
+        for round in 0..2 {
+            // Idle past the keep alive, the connection is kept up in the background meanwhile
+            if round > 0 {
+                tokio::time::sleep(std::time::Duration::from_secs(7)).await;
+                assert!(publisher.is_connected());
+            }
+            let data = MessageWrapper {
+                action: crate::annotations::constants::ACTION_CREATE.clone(),
+                message_type: std::any::type_name::<AnnotationList>(),
+                content: &content,
+            };
+            publisher.publish(data).await.unwrap();
+        }
+        publisher.close().await.unwrap();
+    }
+}
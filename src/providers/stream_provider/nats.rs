@@ -0,0 +1,200 @@
+use std::path::PathBuf;
+use std::time::Duration;
+use crate::config::{NatsStreamConfig, StreamConfig, StreamInfo};
+use crate::providers::hash_provider::Sha256Provider;
+use alvarium_annotator::{HashProvider, MessageWrapper, Publisher};
+use async_nats::{Client, ConnectOptions, ServerAddr};
+use async_nats::jetstream::{self, context::Publish};
+use log::debug;
+use crate::errors::{Error, Result};
+use super::action_name;
+
+pub struct NatsPublisher {
+    cfg: NatsStreamConfig,
+    client: Option<Client>,
+}
+
+impl NatsPublisher {
+    fn subject(&self, msg: &MessageWrapper<'_>) -> Result<String> {
+        Ok(format!("{}.{}", self.cfg.subject_prefix, action_name(msg)?))
+    }
+
+    async fn client(&mut self) -> Result<&Client> {
+        if self.client.is_none() {
+            self.connect().await?;
+        }
+        self.client.as_ref().ok_or_else(|| Error::NatsError("not connected".to_string()))
+    }
+}
+
+#[async_trait::async_trait]
+impl Publisher for NatsPublisher {
+    type StreamConfig = StreamInfo;
+    type Error = crate::errors::Error;
+    async fn new(cfg: &StreamInfo) -> Result<Self> {
+        match &cfg.config {
+            StreamConfig::Nats(cfg) => {
+                Ok(NatsPublisher {
+                    cfg: cfg.clone(),
+                    client: None,
+                })
+            }
+            _ => Err(Error::IncorrectConfig)
+        }
+    }
+
+    async fn close(&mut self) -> Result<()> {
+        if let Some(client) = self.client.take() {
+            client.flush().await.map_err(|e| Error::NatsError(e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    async fn connect(&mut self) -> Result<()> {
+        let servers = self.cfg.servers.iter()
+            .map(|server| server.parse::<ServerAddr>().map_err(|_| Error::IncorrectConfig))
+            .collect::<Result<Vec<ServerAddr>>>()?;
+
+        let client = connect_options(&self.cfg).await?
+            .connect(servers)
+            .await
+            .map_err(|e| Error::NatsError(e.to_string()))?;
+        self.client = Some(client);
+        Ok(())
+    }
+
+    async fn reconnect(&mut self) -> Result<()> {
+        // The client reconnects by itself once it has connected
+        match self.client {
+            Some(_) => Ok(()),
+            None => self.connect().await,
+        }
+    }
+
+    async fn publish(&mut self, msg: MessageWrapper<'_>) -> Result<()> {
+        let subject = self.subject(&msg)?;
+        let bytes = serde_json::to_vec(&msg)?;
+        let jetstream = self.cfg.jetstream.clone();
+        let client = self.client().await?.clone();
+
+        debug!("Publishing to nats subject {}", subject);
+        match jetstream {
+            Some(cfg) => {
+                let mut context = jetstream::new(client);
+                context.set_timeout(Duration::from_millis(cfg.ack_timeout_ms));
+
+                let mut publish = Publish::build();
+                if cfg.dedupe {
+                    publish = publish.message_id(Sha256Provider::new().derive(&bytes));
+                }
+                let ack = context.send_publish(subject, publish.payload(bytes.into()))
+                    .await
+                    .map_err(|e| Error::NatsError(e.to_string()))?;
+                ack.await.map_err(|e| Error::NatsError(e.to_string()))?;
+            }
+            None => {
+                client.publish(subject, bytes.into())
+                    .await
+                    .map_err(|e| Error::NatsError(e.to_string()))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+async fn connect_options(cfg: &NatsStreamConfig) -> Result<ConnectOptions> {
+    let mut options = match (&cfg.credentials_path, &cfg.user, cfg.password()) {
+        (Some(path), _, _) => ConnectOptions::new()
+            .credentials_file(path)
+            .await
+            .map_err(|e| Error::NatsError(e.to_string()))?,
+        (None, Some(user), Some(password)) => ConnectOptions::with_user_and_password(user.clone(), password.to_string()),
+        _ => ConnectOptions::new(),
+    };
+
+    options = options.require_tls(cfg.tls_required);
+    if let Some(ca_path) = &cfg.ca_path {
+        options = options.add_root_certificates(PathBuf::from(ca_path));
+    }
+    if let Some(name) = &cfg.name {
+        options = options.name(name);
+    }
+    Ok(options)
+}
+
+
+#[cfg(test)]
+mod nats_tests {
+    use alvarium_annotator::{Annotator, AnnotationList, MessageWrapper, Publisher};
+    use futures::StreamExt;
+    use crate::annotations::PkiAnnotator;
+    use crate::config::{JetStreamConfig, SdkInfo, Signable, StreamConfig, StreamInfo};
+    use crate::providers::stream_provider::NatsPublisher;
+
+    // Needs a server at localhost:4222, e.g. one started with `nats-server -js`
+    #[tokio::test]
+    #[ignore = "needs a NATS server at localhost:4222"]
+    async fn nats_provider_publish() {
+        let sdk_info: SdkInfo = serde_json::from_slice(crate::CONFIG_BYTES.as_slice()).unwrap();
+        let nats_stream_info: StreamInfo = serde_json::from_slice(crate::NATS_TEST_CONFIG_BYTES.as_slice()).unwrap();
+
+        let mut publisher = NatsPublisher::new(&nats_stream_info).await.unwrap();
+        publisher.connect().await.unwrap();
+        let client = async_nats::connect("nats://localhost:4222").await.unwrap();
+        let mut subscriber = client.subscribe("alvarium.create").await.unwrap();
+        client.flush().await.unwrap();
+
+        let sig = hex::encode([0u8; crypto::signatures::ed25519::SIGNATURE_LENGTH]);
+        let signable = Signable::new("A packet to send to subscribers".to_string(), sig);
+
+        let mut list = AnnotationList { items: vec![] };
+        let mut pki_annotator = PkiAnnotator::new(&sdk_info).unwrap();
+        list.items.push(pki_annotator.annotate(&serde_json::to_vec(&signable).unwrap()).unwrap());
+
+        let data = MessageWrapper {
+            action: crate::annotations::constants::ACTION_CREATE.clone(),
+            message_type: std::any::type_name::<AnnotationList>(),
+            content: &base64::encode(&serde_json::to_vec(&list).unwrap()),
+        };
+        let expected = serde_json::to_vec(&data).unwrap();
+        publisher.publish(data).await.unwrap();
+
+        let received = subscriber.next().await.unwrap();
+        assert_eq!(received.payload.as_ref(), expected.as_slice());
+        publisher.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a NATS server with JetStream at localhost:4222"]
+    async fn jetstream_dedupes_redelivery() {
+        let mut nats_stream_info: StreamInfo = serde_json::from_slice(crate::NATS_TEST_CONFIG_BYTES.as_slice()).unwrap();
+        if let StreamConfig::Nats(cfg) = &mut nats_stream_info.config {
+            cfg.subject_prefix = "alvarium-dedupe".to_string();
+            cfg.jetstream = Some(JetStreamConfig { dedupe: true, ack_timeout_ms: 5000 });
+        }
+
+        let context = async_nats::jetstream::new(async_nats::connect("nats://localhost:4222").await.unwrap());
+        let _ = context.delete_stream("ALVARIUM_DEDUPE").await;
+        let mut stream = context.get_or_create_stream(async_nats::jetstream::stream::Config {
+            name: "ALVARIUM_DEDUPE".to_string(),
+            subjects: vec!["alvarium-dedupe.>".to_string()],
+            ..Default::default()
+        }).await.unwrap();
+
+        let mut publisher = NatsPublisher::new(&nats_stream_info).await.unwrap();
+        publisher.connect().await.unwrap();
+        let content = base64::encode(r#"{"items":[{"key":"some key"}]}"#);
+        for _ in 0..2 {
+            let data = MessageWrapper {
+                action: crate::annotations::constants::ACTION_TRANSIT.clone(),
+                message_type: std::any::type_name::<AnnotationList>(),
+                content: &content,
+            };
+            publisher.publish(data).await.unwrap();
+        }
+
+        assert_eq!(stream.info().await.unwrap().state.messages, 1);
+        context.delete_stream("ALVARIUM_DEDUPE").await.unwrap();
+        publisher.close().await.unwrap();
+    }
+}
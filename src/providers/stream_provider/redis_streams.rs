@@ -0,0 +1,134 @@
+use crate::config::{RedisStreamConfig, StreamConfig, StreamInfo};
+use alvarium_annotator::{MessageWrapper, Publisher};
+use redis::aio::ConnectionManager;
+use redis::Client;
+use log::debug;
+use crate::errors::{Error, Result};
+use super::action_name;
+
+pub struct RedisPublisher {
+    cfg: RedisStreamConfig,
+    client: Client,
+    connection: Option<ConnectionManager>,
+}
+
+impl RedisPublisher {
+    fn xadd(&self, msg: &MessageWrapper<'_>) -> Result<redis::Cmd> {
+        let mut cmd = redis::cmd("XADD");
+        cmd.arg(&self.cfg.stream);
+        if let Some(max_len) = self.cfg.max_len {
+            cmd.arg("MAXLEN");
+            if self.cfg.approximate_trim {
+                cmd.arg("~");
+            }
+            cmd.arg(max_len);
+        }
+
+        let fields = &self.cfg.fields;
+        cmd.arg("*")
+            .arg(&fields.action).arg(action_name(msg)?)
+            .arg(&fields.message_type).arg(msg.message_type)
+            .arg(&fields.content).arg(msg.content);
+        Ok(cmd)
+    }
+}
+
+#[async_trait::async_trait]
+impl Publisher for RedisPublisher {
+    type StreamConfig = StreamInfo;
+    type Error = crate::errors::Error;
+    async fn new(cfg: &StreamInfo) -> Result<Self> {
+        match &cfg.config {
+            StreamConfig::Redis(cfg) => {
+                Ok(RedisPublisher {
+                    cfg: cfg.clone(),
+                    client: Client::open(cfg.url())?,
+                    connection: None,
+                })
+            }
+            _ => Err(Error::IncorrectConfig)
+        }
+    }
+
+    async fn close(&mut self) -> Result<()> {
+        self.connection = None;
+        Ok(())
+    }
+
+    async fn connect(&mut self) -> Result<()> {
+        self.connection = Some(ConnectionManager::new(self.client.clone()).await?);
+        Ok(())
+    }
+
+    async fn reconnect(&mut self) -> Result<()> {
+        // The connection manager reconnects by itself once it has connected
+        match self.connection {
+            Some(_) => Ok(()),
+            None => self.connect().await,
+        }
+    }
+
+    async fn publish(&mut self, msg: MessageWrapper<'_>) -> Result<()> {
+        self.reconnect().await?;
+        let cmd = self.xadd(&msg)?;
+        let connection = self.connection.as_mut().ok_or(Error::IncorrectConfig)?;
+        let id: String = cmd.query_async(connection).await?;
+        debug!("Added entry {} to redis stream {}", id, self.cfg.stream);
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod redis_tests {
+    use std::collections::HashMap;
+    use alvarium_annotator::{AnnotationList, MessageWrapper, Publisher};
+    use crate::config::{StreamConfig, StreamInfo};
+    use crate::providers::stream_provider::RedisPublisher;
+
+    fn message(content: &str) -> MessageWrapper<'_> {
+        MessageWrapper {
+            action: crate::annotations::constants::ACTION_CREATE.clone(),
+            message_type: std::any::type_name::<AnnotationList>(),
+            content,
+        }
+    }
+
+    // Needs a server at localhost:6379, e.g. one started with `docker run -p 6379:6379 redis:7`
+    #[tokio::test]
+    #[ignore = "needs a Redis server at localhost:6379"]
+    async fn redis_provider_publish() {
+        let mut redis_stream_info: StreamInfo = serde_json::from_slice(crate::REDIS_TEST_CONFIG_BYTES.as_slice()).unwrap();
+        if let StreamConfig::Redis(cfg) = &mut redis_stream_info.config {
+            cfg.stream = "alvarium-test".to_string();
+            cfg.max_len = Some(2);
+            cfg.approximate_trim = false;
+        }
+
+        let client = redis::Client::open("redis://localhost:6379").unwrap();
+        let mut connection = client.get_multiplexed_tokio_connection().await.unwrap();
+        redis::cmd("DEL").arg("alvarium-test").query_async::<_, ()>(&mut connection).await.unwrap();
+
+        let mut publisher = RedisPublisher::new(&redis_stream_info).await.unwrap();
+        publisher.connect().await.unwrap();
+        for content in ["first", "second", "third"] {
+            publisher.publish(message(content)).await.unwrap();
+        }
+
+        let len: usize = redis::cmd("XLEN").arg("alvarium-test").query_async(&mut connection).await.unwrap();
+        assert_eq!(len, 2);
+
+        let entries: Vec<(String, HashMap<String, String>)> = redis::cmd("XREVRANGE")
+            .arg("alvarium-test").arg("+").arg("-").arg("COUNT").arg(1)
+            .query_async(&mut connection)
+            .await
+            .unwrap();
+        let (_, fields) = &entries[0];
+        assert_eq!(fields["action"], "create");
+        assert_eq!(fields["type"], std::any::type_name::<AnnotationList>());
+        assert_eq!(fields["annotations"], "third");
+
+        redis::cmd("DEL").arg("alvarium-test").query_async::<_, ()>(&mut connection).await.unwrap();
+        publisher.close().await.unwrap();
+    }
+}
@@ -1,92 +1,198 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use futures::TryFutureExt;
 use crate::config::{SdkInfo, StreamInfo};
-use crate::annotations::AnnotationList;
-use alvarium_annotator::{MessageWrapper, Publisher};
+use crate::annotations::{LineageAnnotationList, LineageAnnotator, Stamp, StampedAnnotation, StampedAnnotationList};
+use alvarium_annotator::{Annotation, MessageWrapper, Publisher};
 use alvarium_annotator::constants::{ACTION_CREATE, ACTION_MUTATE, ACTION_PUBLISH, ACTION_TRANSIT, ANNOTATION_SOURCE};
-use crate::factories::new_annotator;
-use crate::errors::Result;
-use crate::SdkAnnotator;
+use crate::factories::{new_annotator, new_payload_extractor, new_signature_provider};
+use crate::annotations::constants::AnnotationType;
+use crate::providers::payload_provider::PayloadExtractorChain;
+use crate::providers::sign_provider::SignatureProviderWrap;
+use crate::errors::{Error, Result};
+use crate::{SdkAnnotator, SdkAsyncAnnotator};
 
 pub struct SDK<'a, Pub: Publisher> {
-    annotators: &'a mut [Box<SdkAnnotator>],
+    annotators: &'a mut [Box<SdkAsyncAnnotator>],
+    // Annotate the inputs of a mutate or merge, and link them to the output
+    source: Box<SdkAnnotator>,
+    lineage: LineageAnnotator,
     pub cfg: SdkInfo,
+    sign: SignatureProviderWrap,
+    payload: PayloadExtractorChain,
     stream: Pub
 }
 
 impl<'a, Pub: Publisher<StreamConfig = StreamInfo, Error = crate::errors::Error>> SDK<'a, Pub> {
-    pub async fn new(cfg: SdkInfo, annotators: &'a mut [Box<SdkAnnotator>]) -> Result<SDK<'a, Pub>> {
+    /// Creates a new SDK instance. When `cfg.actions` selects annotators per action, each annotator
+    /// is matched to the selection by the annotation type it reports, so every selected type needs
+    /// an annotator of that kind. Synchronous annotators are adapted with
+    /// [`SyncAnnotator`](crate::annotations::SyncAnnotator), or built with
+    /// [`new_pooled_annotator`](crate::factories::new_pooled_annotator) to run in parallel
+    pub async fn new(cfg: SdkInfo, annotators: &'a mut [Box<SdkAsyncAnnotator>]) -> Result<SDK<'a, Pub>> {
+        let kinds = annotators.iter()
+            .map(|annotator| annotator.kind().clone())
+            .collect::<Vec<AnnotationType>>();
+        if !cfg.actions.validate(&kinds) {
+            return Err(Error::IncorrectConfig)
+        }
+        if !cfg.layer.is_base_layer_type() {
+            return Err(Error::UnknownLayer(cfg.layer.0))
+        }
+
+        let source = new_annotator(ANNOTATION_SOURCE.clone(), cfg.clone())?;
+        let lineage = LineageAnnotator::new(&cfg)?;
+        let sign = new_signature_provider(&cfg.signature)?;
+        let payload = new_payload_extractor(&cfg.payload);
         let mut publisher = Pub::new(&cfg.stream).await?;
         publisher.connect().await?;
         Ok(SDK {
             annotators,
+            source,
+            lineage,
             cfg,
+            sign,
+            payload,
             stream: publisher,
         })
     }
 
     pub async fn create(&mut self, data: &[u8]) -> Result<()> {
-        let mut ann_list = AnnotationList::default();
-        for annotator in self.annotators.as_mut() {
-            ann_list.items.push(annotator.annotate(data)?);
-        }
+        let stamp = self.cfg.stamp();
+        self.create_stamped(data, stamp).await
+    }
+
+    /// Same as [`SDK::create`], stamping the annotations with `stamp` instead of the configured
+    /// layer and tag
+    pub async fn create_stamped(&mut self, data: &[u8], stamp: Stamp) -> Result<()> {
+        let selected = self.cfg.actions.create.clone();
+        let annotations = self.annotate(selected.as_deref(), data).await?;
+        let ann_list = self.stamp(annotations, stamp)?;
 
         let ann_bytes = serde_json::to_vec(&ann_list)?;
         let wrapper = MessageWrapper {
             action: ACTION_CREATE.clone(),
-            message_type: std::any::type_name::<AnnotationList>(),
+            message_type: std::any::type_name::<StampedAnnotationList>(),
             content: &base64::encode(ann_bytes)
         };
         Ok(self.stream.publish(wrapper).await?)
     }
 
     pub async fn mutate(&mut self, old: &[u8], new: &[u8]) -> Result<()> {
-        let mut ann_list = AnnotationList::default();
+        self.merge(&[old], new).await
+    }
 
-        let mut source = new_annotator(ANNOTATION_SOURCE.clone(), self.cfg.clone())?;
-        let annotation = source.annotate(old)?;
-        ann_list.items.push(annotation);
+    /// Same as [`SDK::mutate`], stamping the annotations with `stamp` instead of the configured
+    /// layer and tag
+    pub async fn mutate_stamped(&mut self, old: &[u8], new: &[u8], stamp: Stamp) -> Result<()> {
+        self.merge_stamped(&[old], new, stamp).await
+    }
 
-        for annotator in self.annotators.as_mut() {
-            ann_list.items.push(annotator.annotate(new)?);
+    /// Annotates `new` as having been derived from every entry in `old`. Publishes the usual
+    /// annotations together with a signed [`LineageAnnotation`](crate::annotations::LineageAnnotation)
+    /// linking the keys of the inputs to the key of the output, as a single
+    /// [`LineageAnnotationList`]
+    pub async fn merge(&mut self, old: &[&[u8]], new: &[u8]) -> Result<()> {
+        let stamp = self.cfg.stamp();
+        self.merge_stamped(old, new, stamp).await
+    }
+
+    /// Same as [`SDK::merge`], stamping the annotations with `stamp` instead of the configured
+    /// layer and tag
+    pub async fn merge_stamped(&mut self, old: &[&[u8]], new: &[u8], stamp: Stamp) -> Result<()> {
+        let mut annotations = Vec::new();
+        for data in old {
+            annotations.push((self.source.annotate_unsigned(data, &self.payload.extract(data))?, BTreeMap::new()));
         }
 
-        let ann_bytes = serde_json::to_vec(&ann_list)?;
+        let selected = self.cfg.actions.mutate.clone();
+        annotations.extend(self.annotate(selected.as_deref(), new).await?);
+        let ann_list = self.stamp(annotations, stamp.clone())?;
+        let lineage = self.lineage.annotate_lineage(old, new, stamp)?;
+        let lineage_list = LineageAnnotationList { items: ann_list.items, lineage };
+
+        let ann_bytes = serde_json::to_vec(&lineage_list)?;
         let wrapper = MessageWrapper {
             action: ACTION_MUTATE.clone(),
-            message_type: std::any::type_name::<AnnotationList>(),
+            message_type: std::any::type_name::<LineageAnnotationList>(),
             content: &base64::encode(ann_bytes)
         };
         Ok(self.stream.publish(wrapper).await?)
     }
 
     pub async fn transit(&mut self, data: &[u8]) -> Result<()> {
-        let mut ann_list = AnnotationList::default();
-        for annotator in self.annotators.as_mut() {
-            ann_list.items.push(annotator.annotate(data)?);
-        }
+        let stamp = self.cfg.stamp();
+        self.transit_stamped(data, stamp).await
+    }
+
+    /// Same as [`SDK::transit`], stamping the annotations with `stamp` instead of the configured
+    /// layer and tag
+    pub async fn transit_stamped(&mut self, data: &[u8], stamp: Stamp) -> Result<()> {
+        let selected = self.cfg.actions.transit.clone();
+        let annotations = self.annotate(selected.as_deref(), data).await?;
+        let ann_list = self.stamp(annotations, stamp)?;
 
         let ann_bytes = serde_json::to_vec(&ann_list)?;
         let wrapper = MessageWrapper {
             action: ACTION_TRANSIT.clone(),
-            message_type: std::any::type_name::<AnnotationList>(),
+            message_type: std::any::type_name::<StampedAnnotationList>(),
             content: &base64::encode(ann_bytes)
         };
         Ok(self.stream.publish(wrapper).await?)
     }
 
     pub async fn publish(&mut self, data: &[u8]) -> Result<()> {
-        let mut ann_list = AnnotationList::default();
-        for annotator in self.annotators.as_mut() {
-            ann_list.items.push(annotator.annotate(data)?);
-        }
+        let stamp = self.cfg.stamp();
+        self.publish_stamped(data, stamp).await
+    }
+
+    /// Same as [`SDK::publish`], stamping the annotations with `stamp` instead of the configured
+    /// layer and tag
+    pub async fn publish_stamped(&mut self, data: &[u8], stamp: Stamp) -> Result<()> {
+        let selected = self.cfg.actions.publish.clone();
+        let annotations = self.annotate(selected.as_deref(), data).await?;
+        let ann_list = self.stamp(annotations, stamp)?;
 
         let ann_bytes = serde_json::to_vec(&ann_list)?;
         let wrapper = MessageWrapper {
             action: ACTION_PUBLISH.clone(),
-            message_type: std::any::type_name::<AnnotationList>(),
+            message_type: std::any::type_name::<StampedAnnotationList>(),
             content: &base64::encode(ann_bytes)
         };
         Ok(self.stream.publish(wrapper).await?)
     }
+
+    // Runs the annotators whose kind is selected for an action, or all of them if there is no
+    // selection. They run concurrently on a payload extracted once for all of them, the
+    // annotations are returned in annotator order and unsigned, as they are signed once stamped,
+    // each with the context of its annotator
+    async fn annotate(&mut self, selected: Option<&[AnnotationType]>, data: &[u8]) -> Result<Vec<(Annotation, BTreeMap<String, String>)>> {
+        // Copied once to be shared by every annotator, rather than once per pooled annotator
+        let data: Arc<[u8]> = Arc::from(data);
+        let payload = Arc::new(self.payload.extract(&data));
+        let pending = self.annotators.iter_mut()
+            .filter(|annotator| selected.map_or(true, |selected| selected.contains(annotator.kind())))
+            .map(|annotator| {
+                let context = annotator.context();
+                annotator.annotate_unsigned(&data, &payload)
+                    .map_ok(move |annotation| (annotation, context))
+            });
+
+        futures::future::join_all(pending).await
+            .into_iter()
+            .collect()
+    }
+
+    // Layers given for a single call are checked here, as the configured one is in `new`
+    fn stamp(&self, annotations: Vec<(Annotation, BTreeMap<String, String>)>, stamp: Stamp) -> Result<StampedAnnotationList> {
+        if !stamp.layer.is_base_layer_type() {
+            return Err(Error::UnknownLayer(stamp.layer.0))
+        }
+        let items = annotations.into_iter()
+            .map(|(annotation, context)| StampedAnnotation::with_context(annotation, stamp.clone(), context, &self.sign))
+            .collect::<Result<Vec<StampedAnnotation>>>()?;
+        Ok(StampedAnnotationList { items })
+    }
 }
 
 
@@ -97,13 +203,120 @@ mod sdk_tests {
         transport::utangle::Client,
         User,
     };
-    use alvarium_annotator::Publisher;
-    use crate::{config::{SdkInfo, StreamConfig, Signable}, CONFIG_BYTES, providers::stream_provider::IotaPublisher};
-    use crate::factories::new_annotator;
+    use alvarium_annotator::{MessageWrapper, Publisher};
+    use crate::{config::{ActionAnnotators, SdkInfo, StreamConfig, StreamInfo, Signable}, CONFIG_BYTES, providers::stream_provider::IotaPublisher};
+    use crate::annotations::{constants, Annotation, AsyncAnnotator, LineageAnnotationList, LineageAnnotator, Stamp, StampedAnnotationList};
+    use crate::annotations::constants::{AnnotationType, LayerType};
+    use crate::errors::{Error, Result};
+    use crate::factories::{new_annotator, new_async_annotator, new_payload_extractor, new_pooled_annotator, new_signature_provider};
+    use crate::SdkAsyncAnnotator;
     use super::SDK;
 
     const BASE_TOPIC: &'static str = "Base Topic";
 
+    // Keeps the content of every message published, in order
+    struct MemoryPublisher {
+        published: Vec<String>,
+    }
+
+    #[async_trait::async_trait]
+    impl Publisher for MemoryPublisher {
+        type StreamConfig = StreamInfo;
+        type Error = crate::errors::Error;
+        async fn new(_cfg: &StreamInfo) -> Result<Self> {
+            Ok(MemoryPublisher { published: Vec::new() })
+        }
+
+        async fn close(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        async fn connect(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        async fn reconnect(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        async fn publish(&mut self, msg: MessageWrapper<'_>) -> Result<()> {
+            self.published.push(msg.content.to_string());
+            Ok(())
+        }
+    }
+
+    // Produces an annotation of a fixed kind, as a custom annotator would
+    struct KindAnnotator(AnnotationType);
+
+    #[async_trait::async_trait]
+    impl AsyncAnnotator for KindAnnotator {
+        fn kind(&self) -> &AnnotationType {
+            &self.0
+        }
+
+        async fn annotate(&mut self, _data: &[u8]) -> Result<Annotation> {
+            Ok(Annotation::new("key", constants::SHA256_HASH.clone(), "host", self.0.clone(), true))
+        }
+    }
+
+    fn published_kinds(content: &str) -> Vec<AnnotationType> {
+        let list: StampedAnnotationList = serde_json::from_slice(&base64::decode(content).unwrap()).unwrap();
+        list.items.into_iter().map(|item| item.annotation.kind).collect()
+    }
+
+    #[tokio::test]
+    async fn sdk_actions_select_annotators_by_kind() {
+        let mut sdk_info: SdkInfo = serde_json::from_slice(CONFIG_BYTES.as_slice()).unwrap();
+        sdk_info.annotators = vec![
+            constants::ANNOTATION_PKI.clone(),
+            constants::ANNOTATION_SOURCE.clone(),
+            constants::ANNOTATION_TPM.clone(),
+        ];
+        sdk_info.actions = ActionAnnotators {
+            create: Some(vec![constants::ANNOTATION_TPM.clone(), constants::ANNOTATION_PKI.clone()]),
+            mutate: None,
+            transit: Some(vec![constants::ANNOTATION_SOURCE.clone()]),
+            publish: Some(vec![]),
+        };
+
+        // Built in a different order from the configured annotators
+        let mut annotators = [&*constants::ANNOTATION_SOURCE, &*constants::ANNOTATION_TPM, &*constants::ANNOTATION_PKI]
+            .into_iter()
+            .map(|kind| Box::new(KindAnnotator(kind.clone())) as Box<SdkAsyncAnnotator>)
+            .collect::<Vec<_>>();
+        let mut sdk: SDK<MemoryPublisher> = SDK::new(sdk_info.clone(), annotators.as_mut_slice()).await.unwrap();
+
+        sdk.create(b"data").await.unwrap();
+        sdk.transit(b"data").await.unwrap();
+        sdk.publish(b"data").await.unwrap();
+
+        let published = &sdk.stream.published;
+        assert_eq!(published_kinds(&published[0]), vec![constants::ANNOTATION_TPM.clone(), constants::ANNOTATION_PKI.clone()]);
+        assert_eq!(published_kinds(&published[1]), vec![constants::ANNOTATION_SOURCE.clone()]);
+        assert!(published_kinds(&published[2]).is_empty());
+
+        // A selected kind without an annotator of that kind is rejected
+        let mut annotators = vec![Box::new(KindAnnotator(constants::ANNOTATION_SOURCE.clone())) as Box<SdkAsyncAnnotator>];
+        assert!(SDK::<MemoryPublisher>::new(sdk_info, annotators.as_mut_slice()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn sdk_rejects_unknown_layer() {
+        let mut sdk_info: SdkInfo = serde_json::from_slice(CONFIG_BYTES.as_slice()).unwrap();
+        sdk_info.annotators = vec![constants::ANNOTATION_SOURCE.clone()];
+        let mut annotators = vec![Box::new(KindAnnotator(constants::ANNOTATION_SOURCE.clone())) as Box<SdkAsyncAnnotator>];
+
+        sdk_info.layer = LayerType("aap".to_string());
+        let sdk = SDK::<MemoryPublisher>::new(sdk_info.clone(), annotators.as_mut_slice()).await;
+        assert!(matches!(sdk, Err(Error::UnknownLayer(layer)) if layer == "aap"));
+
+        sdk_info.layer = constants::LAYER_CICD.clone();
+        let mut sdk: SDK<MemoryPublisher> = SDK::new(sdk_info, annotators.as_mut_slice()).await.unwrap();
+        let stamp = Stamp::new(LayerType("hots".to_string()), None);
+        assert!(matches!(sdk.create_stamped(b"data", stamp).await, Err(Error::UnknownLayer(_))));
+        assert!(sdk.stream.published.is_empty());
+    }
+
     #[tokio::test]
     async fn sdk_create_transit_publish() {
         // Uses base CONFIG_BYTES pulled from local config file (or the resources/test_config.json
@@ -113,14 +326,18 @@ mod sdk_tests {
 
         let mut annotators = Vec::new();
         for ann in &sdk_info.annotators {
-            let annotator = new_annotator(ann.clone(), sdk_info.clone()).unwrap();
+            let annotator = new_async_annotator(ann.clone(), sdk_info.clone()).unwrap();
             annotators.push(annotator)
         }
 
         // Mocks SDK::new() without Pub::connect()
         let mut sdk = SDK {
             annotators: annotators.as_mut_slice(),
+            source: new_annotator(constants::ANNOTATION_SOURCE.clone(), sdk_info.clone()).unwrap(),
+            lineage: LineageAnnotator::new(&sdk_info).unwrap(),
             cfg: sdk_info.clone(),
+            sign: new_signature_provider(&sdk_info.signature).unwrap(),
+            payload: new_payload_extractor(&sdk_info.payload),
             stream: publisher,
         };
 
@@ -130,6 +347,9 @@ mod sdk_tests {
         sdk.create(signable.to_bytes().as_slice()).await.unwrap();
         sdk.transit(signable.to_bytes().as_slice()).await.unwrap();
         sdk.publish(signable.to_bytes().as_slice()).await.unwrap();
+
+        let stamp = Stamp::new(constants::LAYER_HOST.clone(), Some("A correlation tag".to_string()));
+        sdk.transit_stamped(signable.to_bytes().as_slice(), stamp).await.unwrap();
         std::fs::remove_file("temp_file").unwrap();
     }
 
@@ -142,14 +362,18 @@ mod sdk_tests {
 
         let mut annotators = Vec::new();
         for ann in &sdk_info.annotators {
-            let annotator = new_annotator(ann.clone(), sdk_info.clone()).unwrap();
+            let annotator = new_pooled_annotator(ann.clone(), sdk_info.clone()).unwrap();
             annotators.push(annotator)
         }
 
         // Mocks SDK::new() without Pub::connect()
         let mut sdk = SDK {
             annotators: annotators.as_mut_slice(),
+            source: new_annotator(constants::ANNOTATION_SOURCE.clone(), sdk_info.clone()).unwrap(),
+            lineage: LineageAnnotator::new(&sdk_info).unwrap(),
             cfg: sdk_info.clone(),
+            sign: new_signature_provider(&sdk_info.signature).unwrap(),
+            payload: new_payload_extractor(&sdk_info.payload),
             stream: publisher,
         };
 
@@ -158,9 +382,34 @@ mod sdk_tests {
         let sig = hex::encode([0u8; crypto::signatures::ed25519::SIGNATURE_LENGTH]);
         let signable = Signable::new(data, sig);
         sdk.mutate(old_data.as_bytes(), signable.to_bytes().as_slice()).await.unwrap();
+
+        let other_data = "Another input merged into the data".to_string();
+        sdk.merge(&[old_data.as_bytes(), other_data.as_bytes()], signable.to_bytes().as_slice()).await.unwrap();
         std::fs::remove_file("temp_file").unwrap();
     }
 
+    #[tokio::test]
+    async fn sdk_merge_publishes_lineage_with_annotations() {
+        let mut sdk_info: SdkInfo = serde_json::from_slice(CONFIG_BYTES.as_slice()).unwrap();
+        sdk_info.actions = ActionAnnotators::default();
+        let mut annotators = vec![Box::new(KindAnnotator(constants::ANNOTATION_TPM.clone())) as Box<SdkAsyncAnnotator>];
+        let mut sdk: SDK<MemoryPublisher> = SDK::new(sdk_info, annotators.as_mut_slice()).await.unwrap();
+
+        let inputs: [&[u8]; 2] = [b"first input", b"second input"];
+        sdk.merge(&inputs, b"merged output").await.unwrap();
+
+        assert_eq!(sdk.stream.published.len(), 1);
+        let bytes = base64::decode(&sdk.stream.published[0]).unwrap();
+        let list: LineageAnnotationList = serde_json::from_slice(&bytes).unwrap();
+        let kinds = list.items.iter().map(|item| item.annotation.kind.clone()).collect::<Vec<_>>();
+        assert_eq!(kinds, vec![
+            constants::ANNOTATION_SOURCE.clone(),
+            constants::ANNOTATION_SOURCE.clone(),
+            constants::ANNOTATION_TPM.clone(),
+        ]);
+        assert_eq!(list.lineage.parents, vec![list.items[0].annotation.key.clone(), list.items[1].annotation.key.clone()]);
+    }
+
     // Mocks Pub::new() with IotaPublisher Annotator
     async fn mock_annotator(sdk_info: SdkInfo) -> IotaPublisher {
         if let StreamConfig::IotaStreams(config) = &sdk_info.stream.config {